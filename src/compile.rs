@@ -0,0 +1,44 @@
+use crate::storage::Storage;
+use anyhow::Result;
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CompileOrder {
+    /// Alphabetical by filename
+    Name,
+    /// Oldest change first
+    Modified,
+}
+
+/// All drafts joined into one document, each under a heading made from its
+/// filename. `markdown` picks `#` headings and `---` rules over plain text ones.
+pub fn compile_drafts(order: CompileOrder, markdown: bool) -> Result<String> {
+    let drafts = match order {
+        CompileOrder::Name => Storage::list_drafts()?,
+        CompileOrder::Modified => {
+            let mut drafts = Storage::list_recent_drafts(usize::MAX)?;
+            drafts.reverse();
+            drafts
+        }
+    };
+
+    let separator = if markdown { "\n\n---\n\n" } else { "\n\n* * *\n\n" };
+    let mut sections = Vec::new();
+    for name in drafts {
+        let content = Storage::load_draft(&name)?;
+        let title = title_from_filename(&name);
+        let heading = if markdown {
+            format!("# {}", title)
+        } else {
+            format!("{}\n{}", title, "=".repeat(title.chars().count()))
+        };
+        sections.push(format!("{}\n\n{}", heading, content.trim()));
+    }
+    Ok(sections.join(separator) + "\n")
+}
+
+/// "chapter_one-draft.md" -> "chapter one draft"
+fn title_from_filename(name: &str) -> String {
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    stem.replace(['_', '-'], " ")
+}
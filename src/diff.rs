@@ -0,0 +1,48 @@
+use similar::{ChangeTag, TextDiff};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DiffKind {
+    Same,
+    Added,
+    Removed,
+}
+
+/// Line-based diff from `old` to `new`, one entry per line without the
+/// trailing newline.
+pub fn line_diff(old: &str, new: &str) -> Vec<(DiffKind, String)> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| {
+            let kind = match change.tag() {
+                ChangeTag::Equal => DiffKind::Same,
+                ChangeTag::Insert => DiffKind::Added,
+                ChangeTag::Delete => DiffKind::Removed,
+            };
+            (kind, change.value().trim_end_matches(['\n', '\r']).to_string())
+        })
+        .collect()
+}
+
+/// Word-level diff from `old` to `new`, split back into lines of segments so
+/// changes inside a long paragraph stand out.
+pub fn word_diff(old: &str, new: &str) -> Vec<Vec<(DiffKind, String)>> {
+    let mut lines = vec![Vec::new()];
+    for change in TextDiff::from_words(old, new).iter_all_changes() {
+        let kind = match change.tag() {
+            ChangeTag::Equal => DiffKind::Same,
+            ChangeTag::Insert => DiffKind::Added,
+            ChangeTag::Delete => DiffKind::Removed,
+        };
+        for (i, part) in change.value().split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Vec::new());
+            }
+            if !part.is_empty() {
+                if let Some(line) = lines.last_mut() {
+                    line.push((kind, part.to_string()));
+                }
+            }
+        }
+    }
+    lines
+}
@@ -0,0 +1,51 @@
+use ratatui::style::{Color, Style};
+use ratatui::text::Line;
+
+fn plain_block(code: &str) -> Vec<Line<'static>> {
+    code.lines()
+        .map(|l| Line::styled(l.to_string(), Style::default().fg(Color::Gray)))
+        .collect()
+}
+
+/// Lines for a fenced code block. Highlighted when built with the `highlight`
+/// feature and the fence names a known language, plain gray otherwise.
+#[cfg(feature = "highlight")]
+pub fn code_block(code: &str, lang: &str) -> Vec<Line<'static>> {
+    use std::sync::OnceLock;
+    use ratatui::text::Span;
+    use syntect::easy::HighlightLines;
+    use syntect::highlighting::{Theme, ThemeSet};
+    use syntect::parsing::SyntaxSet;
+    use syntect::util::LinesWithEndings;
+
+    // Loading the bundled definitions is slow, so do it once
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    let syntaxes = SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines);
+    let theme = THEME.get_or_init(|| ThemeSet::load_defaults().themes["base16-ocean.dark"].clone());
+
+    let Some(syntax) = syntaxes.find_syntax_by_token(lang) else {
+        return plain_block(code);
+    };
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut lines = Vec::new();
+    for line in LinesWithEndings::from(code) {
+        let Ok(ranges) = highlighter.highlight_line(line, syntaxes) else {
+            return plain_block(code);
+        };
+        let spans: Vec<Span> = ranges
+            .into_iter()
+            .map(|(style, text)| {
+                let fg = style.foreground;
+                Span::styled(text.trim_end_matches(['\n', '\r']).to_string(), Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b)))
+            })
+            .collect();
+        lines.push(Line::from(spans));
+    }
+    lines
+}
+
+#[cfg(not(feature = "highlight"))]
+pub fn code_block(code: &str, _lang: &str) -> Vec<Line<'static>> {
+    plain_block(code)
+}
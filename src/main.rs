@@ -2,50 +2,128 @@ use anyhow::Result;
 use app::App;
 use clap::{Parser, Subcommand};
 use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::ExecutableCommand;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 mod app;
+mod compile;
+mod diff;
+mod highlight;
+mod markdown;
 mod storage;
 mod tui;
 mod ui;
 mod spellcheck;
+mod splash;
+mod stats;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Insert the contents of a file ("-" for stdin) into the buffer at launch
+    #[arg(long, global = true, value_name = "PATH")]
+    import: Option<String>,
+
+    /// Read and save settings in this file instead of the default location
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Browse without changing anything: saves, renames, deletes and settings changes are refused
+    #[arg(long, global = true)]
+    read_only: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Start a distraction-free flow session
     Flow {
-        /// Duration in minutes
-        #[arg(long, default_value_t = 10)]
-        time: u64,
+        /// Duration in minutes (defaults to the last one given, initially 10)
+        #[arg(long)]
+        time: Option<u64>,
+        /// Number of finished sentences to aim for
+        #[arg(long, value_name = "N")]
+        sentences: Option<usize>,
     },
     /// View flow history
-    FlowHistory,
+    FlowHistory {
+        /// Print the history as JSON instead of opening it
+        #[arg(long)]
+        json: bool,
+    },
+    /// Combine every draft into one document, each under its own heading
+    Compile {
+        /// File to write; prints to stdout when omitted
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+        /// Order the drafts appear in
+        #[arg(long, value_enum, default_value_t = compile::CompileOrder::Name)]
+        order: compile::CompileOrder,
+    },
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    storage::Storage::set_read_only(cli.read_only);
+
+    if let Some(path) = &cli.config {
+        storage::Storage::use_settings_file(path)?;
+    }
+
+    if let Some(Commands::Compile { out, order }) = &cli.command {
+        // Markdown headings unless the output is plain text
+        let markdown = match out.as_ref().and_then(|p| p.extension()) {
+            Some(ext) => ext == "md" || ext == "markdown",
+            None => storage::Storage::load_settings().map(|s| s.default_extension == "md").unwrap_or(false),
+        };
+        let document = compile::compile_drafts(*order, markdown)?;
+        match out {
+            Some(path) => {
+                std::fs::write(path, document)?;
+                eprintln!("Compiled drafts into {}", path.display());
+            }
+            None => print!("{}", document),
+        }
+        return Ok(());
+    }
+
+    if let Some(Commands::FlowHistory { json: true }) = &cli.command {
+        let history = storage::Storage::load_flow_history()?;
+        println!("{}", serde_json::to_string_pretty(&history)?);
+        return Ok(());
+    }
 
     let mut app = match cli.command {
-        Some(Commands::Flow { time }) => App::with_flow_mode(time),
-        Some(Commands::FlowHistory) => {
+        Some(Commands::Flow { time, sentences }) => App::with_flow_mode(time, sentences),
+        Some(Commands::FlowHistory { .. }) => {
             let mut app = App::new();
             // Simulate 'h' from Menu to enter History mode properly
             app.handle_key_event(crossterm::event::KeyEvent::new(KeyCode::Char('h'), KeyModifiers::NONE));
             app
         },
-        None => App::new(),
+        Some(Commands::Compile { .. }) | None => App::new(),
     };
 
+    // Read before entering raw mode so stdin imports behave. Piped input is
+    // picked up without --import, e.g. `cat notes.txt | writeapp flow`
+    let piped = cli.import.is_none() && !std::io::stdin().is_terminal();
+    if let Some(path) = cli.import.as_deref().or(piped.then_some("-")) {
+        app.import_at_launch(path);
+    }
+
     let mut terminal = tui::init()?;
     let app_result = run_app(&mut terminal, &mut app);
     tui::restore()?;
+    if app.settings.print_summary {
+        println!("{}", app.session_summary());
+    }
     app_result
 }
 
@@ -53,16 +131,41 @@ fn run_app(terminal: &mut tui::Tui, app: &mut App) -> Result<()> {
     let tick_rate = Duration::from_millis(250);
     let mut last_tick = Instant::now();
 
+    // Raw mode turns Ctrl+C into a key event, so these only fire for signals
+    // from outside (kill, a closing terminal or session manager)
+    let terminate = Arc::new(AtomicBool::new(false));
+    for signal in [SIGTERM, SIGINT] {
+        signal_hook::flag::register(signal, Arc::clone(&terminate))?;
+    }
+    #[cfg(unix)]
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(&terminate))?;
+
+    let mut cursor_style = None;
+
     while !app.should_quit {
+        if terminate.load(Ordering::Relaxed) {
+            app.save_before_exit();
+            break;
+        }
+
         terminal.draw(|f| ui::ui(f, app))?;
 
+        // Only write the escape sequence when the shape actually changes
+        let style = app.cursor_style();
+        if cursor_style != Some(std::mem::discriminant(&style)) {
+            terminal.backend_mut().execute(style)?;
+            cursor_style = Some(std::mem::discriminant(&style));
+        }
+
         let timeout = tick_rate
             .checked_sub(last_tick.elapsed())
             .unwrap_or_else(|| Duration::from_secs(0));
 
         if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                app.handle_key_event(key);
+            match event::read()? {
+                Event::Key(key) => app.handle_key_event(key),
+                Event::Paste(text) => app.handle_paste(&text),
+                _ => {}
             }
         }
 
@@ -0,0 +1,204 @@
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone)]
+pub struct Heading {
+    /// 1 for `#`, 2 for `##`, ...
+    pub level: usize,
+    pub text: String,
+    /// Zero-based buffer line the heading starts on
+    pub line: usize,
+}
+
+/// Extracts every heading in `text` along with the line it appears on
+pub fn headings(text: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut current: Option<Heading> = None;
+
+    for (event, range) in Parser::new(text).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                current = Some(Heading {
+                    level: heading_level(level),
+                    text: String::new(),
+                    line: text[..range.start].matches('\n').count(),
+                });
+            }
+            Event::Text(t) | Event::Code(t) => {
+                if let Some(heading) = current.as_mut() {
+                    heading.text.push_str(&t);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(heading) = current.take() {
+                    headings.push(heading);
+                }
+            }
+            _ => {}
+        }
+    }
+    headings
+}
+
+/// Whether line `row` is part of a fenced code block, fence lines included.
+/// Scans line by line rather than parsing, since it runs on every keystroke.
+pub fn in_code_fence(lines: &[String], row: usize) -> bool {
+    // The marker character and length that opened the current block
+    let mut open: Option<(char, usize)> = None;
+    for (i, line) in lines.iter().enumerate().take(row + 1) {
+        let trimmed = line.trim_start();
+        let marker = match trimmed.chars().next() {
+            Some(c @ ('`' | '~')) => c,
+            _ => continue,
+        };
+        let len = trimmed.chars().take_while(|&c| c == marker).count();
+        if len < 3 {
+            continue;
+        }
+        open = match open {
+            None => Some((marker, len)),
+            // A closing fence is at least as long as the opening one, with nothing after it
+            Some((m, l)) if m == marker && len >= l && trimmed[len..].trim().is_empty() => None,
+            still_open => still_open,
+        };
+        if i == row {
+            // Either an opening or a closing fence
+            return true;
+        }
+    }
+    open.is_some()
+}
+
+/// Tidies markdown for publishing: trims trailing spaces (keeping the two
+/// that make a hard line break), collapses runs of
+/// blank lines, writes every bullet as `-` and puts blank lines around
+/// headings. Code blocks are left alone. Also returns where each original
+/// line ended up, so callers can keep the cursor and folds in place.
+pub fn tidy(lines: &[String]) -> (Vec<String>, Vec<usize>) {
+    let text = lines.join("\n");
+    let line_of = |offset: usize| text[..offset].matches('\n').count();
+
+    let mut code = HashSet::new();
+    let mut heading_starts = HashSet::new();
+    let mut heading_ends = HashSet::new();
+    // Line to byte column of each bullet marker
+    let mut bullets = HashMap::new();
+    // Whether each enclosing list is a bullet list
+    let mut lists = Vec::new();
+    // Lines ending in a hard break, which their trailing spaces make
+    let mut hard_breaks = HashSet::new();
+
+    for (event, range) in Parser::new(&text).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                code.extend(line_of(range.start)..=line_of(text[..range.end].trim_end_matches('\n').len()));
+            }
+            Event::Start(Tag::Heading { .. }) => {
+                heading_starts.insert(line_of(range.start));
+                heading_ends.insert(line_of(text[..range.end].trim_end_matches('\n').len()));
+            }
+            Event::HardBreak if text[range.clone()].starts_with(' ') => {
+                hard_breaks.insert(line_of(range.start));
+            }
+            Event::Start(Tag::List(first)) => lists.push(first.is_none()),
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+            }
+            Event::Start(Tag::Item) if lists.last() == Some(&true) => {
+                let line = line_of(range.start);
+                let line_start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+                bullets.insert(line, range.start - line_start);
+            }
+            _ => {}
+        }
+    }
+
+    let mut out: Vec<String> = Vec::new();
+    let mut moved_to = Vec::with_capacity(lines.len());
+    let mut blank_before_next = false;
+    for (i, line) in lines.iter().enumerate() {
+        if code.contains(&i) {
+            moved_to.push(out.len());
+            out.push(line.clone());
+            continue;
+        }
+        let mut line = line.trim_end().to_string();
+        if hard_breaks.contains(&i) {
+            line.push_str("  ");
+        }
+        if line.is_empty() {
+            moved_to.push(out.len().saturating_sub(1));
+            if out.last().is_some_and(|l| !l.is_empty()) {
+                out.push(String::new());
+                moved_to[i] = out.len() - 1;
+            }
+            blank_before_next = false;
+            continue;
+        }
+        if let Some(&col) = bullets.get(&i) {
+            if matches!(line.as_bytes().get(col), Some(b'*' | b'+')) {
+                line.replace_range(col..col + 1, "-");
+            }
+        }
+        if (blank_before_next || heading_starts.contains(&i)) && out.last().is_some_and(|l| !l.is_empty()) {
+            out.push(String::new());
+        }
+        blank_before_next = heading_ends.contains(&i);
+        moved_to.push(out.len());
+        out.push(line);
+    }
+
+    while out.len() > 1 && out.last().is_some_and(|l| l.is_empty()) {
+        out.pop();
+    }
+    if out.is_empty() {
+        out.push(String::new());
+    }
+    let last = out.len() - 1;
+    for row in &mut moved_to {
+        *row = (*row).min(last);
+    }
+    (out, moved_to)
+}
+
+fn heading_level(level: HeadingLevel) -> usize {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn tidy_keeps_hard_line_breaks() {
+        let (out, _) = tidy(&lines("first line   \nsecond line  \n\nnext paragraph "));
+        assert_eq!(out, ["first line  ", "second line", "", "next paragraph"]);
+    }
+
+    #[test]
+    fn code_fence_covers_its_fences_and_body() {
+        let text = lines("prose\n```rust\nlet x = 1;\n```\nmore prose");
+        let inside: Vec<bool> = (0..text.len()).map(|row| in_code_fence(&text, row)).collect();
+        assert_eq!(inside, [false, true, true, true, false]);
+    }
+
+    #[test]
+    fn code_fence_closes_only_on_a_matching_fence() {
+        // A shorter fence, another marker or trailing text don't close it
+        let text = lines("````\n```\n~~~~\n```` not a close\nstill code\n````\nprose");
+        assert!(in_code_fence(&text, 4));
+        assert!(in_code_fence(&text, 5));
+        assert!(!in_code_fence(&text, 6));
+    }
+}
@@ -1,3 +1,4 @@
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
 use std::collections::HashSet;
 
 /// Basic spell checker - flags words not in a common word list
@@ -112,39 +113,141 @@ impl SpellChecker {
             "speech", "nature", "range", "steam", "motion", "path", "liquid", "log", "meant",
             "quotient", "teeth", "shell", "neck",
         ];
+
+        // Contractions are looked up whole since their stems ("don", "ll") aren't words
+        let contractions: &[&str] = &[
+            "i'm", "i've", "i'll", "i'd", "you're", "you've", "you'll", "you'd", "he's", "he'll",
+            "he'd", "she's", "she'll", "she'd", "it's", "it'll", "it'd", "we're", "we've", "we'll",
+            "we'd", "they're", "they've", "they'll", "they'd", "that's", "that'll", "there's",
+            "here's", "what's", "who's", "where's", "how's", "let's", "don't", "doesn't", "didn't",
+            "isn't", "aren't", "wasn't", "weren't", "haven't", "hasn't", "hadn't", "won't",
+            "wouldn't", "can't", "couldn't", "shouldn't", "mustn't", "needn't", "ain't", "y'all",
+        ];
         
         Self {
-            common_words: words.iter().copied().collect(),
+            common_words: words.iter().chain(contractions).copied().collect(),
         }
     }
 
     pub fn check_text(&self, text: &str) -> HashSet<String> {
         let mut misspelled = HashSet::new();
         
-        for word in text.split_whitespace() {
-            // Clean the word - remove punctuation
-            let cleaned: String = word
-                .chars()
-                .filter(|c| c.is_alphabetic() || *c == '\'')
-                .collect();
-            
-            if cleaned.is_empty() || cleaned.len() < 2 {
+        for token in text.split_whitespace() {
+            if looks_like_url(token) {
                 continue;
             }
-            
-            let lower = cleaned.to_lowercase();
-            
-            // Skip if in common words list
-            if self.common_words.contains(lower.as_str()) {
-                continue;
+            // Hyphenated compounds and slash pairs are checked part by part
+            for part in token.split(['-', '–', '—', '/']) {
+                if let Some(word) = self.check_word(part) {
+                    misspelled.insert(word);
+                }
             }
-            
-            // Flag as potentially misspelled
-            misspelled.insert(cleaned);
         }
         
         misspelled
     }
+
+    /// Like `check_text`, but only looks at markdown prose: code spans, code
+    /// blocks, raw HTML and link destinations are skipped.
+    pub fn check_markdown(&self, text: &str) -> HashSet<String> {
+        let mut prose = String::new();
+        let mut in_code_block = false;
+
+        for event in Parser::new(text) {
+            match event {
+                Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+                Event::End(TagEnd::CodeBlock) => in_code_block = false,
+                Event::Text(t) if !in_code_block => prose.push_str(&t),
+                // Everything else (code, html, breaks, block ends) separates words
+                _ => prose.push(' '),
+            }
+        }
+
+        self.check_text(&prose)
+    }
+
+    /// Returns the word as written if it should be flagged
+    fn check_word(&self, raw: &str) -> Option<String> {
+        // Strip surrounding punctuation but keep apostrophes inside the word,
+        // and the one ending a plural possessive ("writers'")
+        let not_word = |c: char| !c.is_alphanumeric();
+        let start = raw.len() - raw.trim_start_matches(not_word).len();
+        let mut word = raw[start..].trim_end_matches(not_word);
+        let end = start + word.len();
+        if let Some(c) = raw[end..].chars().next().filter(|&c| is_apostrophe(c) && word.ends_with('s')) {
+            word = &raw[start..end + c.len_utf8()];
+        }
+        
+        if word.chars().count() < 2 {
+            return None;
+        }
+        
+        // Numbers, codes like "v2", and dotted tokens like "e.g" or file names
+        if !word.chars().all(|c| c.is_alphabetic() || is_apostrophe(c)) {
+            return None;
+        }
+        
+        // CamelCase and ACRONYMS are most likely intentional
+        if word.chars().skip(1).any(char::is_uppercase) {
+            return None;
+        }
+        
+        let lower: String = word
+            .chars()
+            .map(|c| if is_apostrophe(c) { '\'' } else { fold_accent(c) })
+            .collect::<String>()
+            .to_lowercase();
+        
+        if self.is_known(&lower) {
+            None
+        } else {
+            Some(word.to_string())
+        }
+    }
+
+    fn is_known(&self, lower: &str) -> bool {
+        if self.common_words.contains(lower) {
+            return true;
+        }
+        // Possessives: "writer's", "writers'"
+        match lower.strip_suffix("'s").or_else(|| lower.strip_suffix('\'')) {
+            Some(base) => self.common_words.contains(base) || self.common_words.contains(base.trim_end_matches('s')),
+            None => false,
+        }
+    }
+}
+
+fn is_apostrophe(c: char) -> bool {
+    matches!(c, '\'' | '’' | '‘')
+}
+
+fn looks_like_url(token: &str) -> bool {
+    token.contains("://")
+        || token.starts_with("www.")
+        || token.starts_with("mailto:")
+        || (token.contains('@') && token.contains('.'))
+}
+
+/// Maps common accented Latin letters to their base letter so "café" matches "cafe"
+fn fold_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' => 'A',
+        'ç' => 'c',
+        'Ç' => 'C',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'È' | 'É' | 'Ê' | 'Ë' => 'E',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'Ì' | 'Í' | 'Î' | 'Ï' => 'I',
+        'ñ' => 'n',
+        'Ñ' => 'N',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' => 'o',
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' => 'O',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'Ù' | 'Ú' | 'Û' | 'Ü' => 'U',
+        'ý' | 'ÿ' => 'y',
+        _ => c,
+    }
 }
 
 impl Default for SpellChecker {
@@ -152,3 +255,44 @@ impl Default for SpellChecker {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn misspelled(text: &str) -> Vec<String> {
+        let mut words: Vec<String> = SpellChecker::new().check_text(text).into_iter().collect();
+        words.sort();
+        words
+    }
+
+    #[test]
+    fn contractions_are_known_words() {
+        assert!(misspelled("I don't think they'll say it's wrong").is_empty());
+        // Curly apostrophes from word processors count the same
+        assert!(misspelled("You can’t go").is_empty());
+    }
+
+    #[test]
+    fn possessives_check_their_base_word() {
+        assert!(misspelled("the house's door and the horses' field").is_empty());
+        assert_eq!(misspelled("the zorble's door"), vec!["zorble's"]);
+    }
+
+    #[test]
+    fn hyphenated_words_are_checked_part_by_part() {
+        assert!(misspelled("an old-world story").is_empty());
+        assert_eq!(misspelled("an old-wurld story"), vec!["wurld"]);
+    }
+
+    #[test]
+    fn surrounding_punctuation_is_ignored() {
+        assert!(misspelled("(Yes), \"the\" end; stop! Why?").is_empty());
+        assert_eq!(misspelled("\"qwertyx,\""), vec!["qwertyx"]);
+    }
+
+    #[test]
+    fn urls_numbers_and_acronyms_are_skipped() {
+        assert!(misspelled("see https://example.com/xyzzy or v2 of NASA's HTTPServer").is_empty());
+    }
+}
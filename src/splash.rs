@@ -0,0 +1,63 @@
+use crate::storage::{SplashContent, Settings, Storage};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const QUOTES: &[&str] = &[
+    "\"Start writing, no matter what. The water does not flow until the faucet is turned on.\" — Louis L'Amour",
+    "\"You can't use up creativity. The more you use, the more you have.\" — Maya Angelou",
+    "\"There is no greater agony than bearing an untold story inside you.\" — Maya Angelou",
+    "\"If there's a book that you want to read, but it hasn't been written yet, then you must write it.\" — Toni Morrison",
+    "\"The first draft is just you telling yourself the story.\" — Terry Pratchett",
+    "\"You can always edit a bad page. You can't edit a blank page.\" — Jodi Picoult",
+    "\"Fill your paper with the breathings of your heart.\" — William Wordsworth",
+    "\"Write what should not be forgotten.\" — Isabel Allende",
+    "\"One day I will find the right words, and they will be simple.\" — Jack Kerouac",
+    "\"Either write something worth reading or do something worth writing.\" — Benjamin Franklin",
+];
+
+/// The extra line shown under the title, or `None` for the classic credits.
+pub fn splash_line(settings: &Settings) -> Option<String> {
+    match settings.splash_content {
+        SplashContent::Credits => None,
+        SplashContent::Quote => Some(random_quote(settings)),
+        SplashContent::Stats => Some(last_session_summary(settings)),
+    }
+}
+
+fn random_quote(settings: &Settings) -> String {
+    // User quotes come from a plain text file, one per line
+    let custom: Vec<String> = if settings.splash_quotes_file.is_empty() {
+        Vec::new()
+    } else {
+        std::fs::read_to_string(&settings.splash_quotes_file)
+            .map(|c| c.lines().map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect())
+            .unwrap_or_default()
+    };
+
+    // Good enough randomness for picking a quote without pulling in a crate
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as usize)
+        .unwrap_or(0);
+
+    if custom.is_empty() {
+        QUOTES[seed % QUOTES.len()].to_string()
+    } else {
+        custom[seed % custom.len()].clone()
+    }
+}
+
+fn last_session_summary(settings: &Settings) -> String {
+    let history = Storage::load_flow_history().unwrap_or_default();
+    match history.first() {
+        Some(entry) => {
+            let words = crate::stats::count_words(&entry.text);
+            format!(
+                "Last session: {} words in {} min on {}",
+                settings.number_format.format(words),
+                entry.duration_minutes,
+                entry.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d")
+            )
+        }
+        None => "No flow sessions yet — press f from the menu to start one".to_string(),
+    }
+}
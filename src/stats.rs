@@ -0,0 +1,61 @@
+use crate::storage::FlowEntry;
+use chrono::{Days, NaiveDate};
+use std::collections::BTreeMap;
+
+/// Words in `text`, counted the same way everywhere: the editor's status
+/// bar, the drafts list and the history all agree
+pub fn count_words(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// Words written per local calendar day: flow sessions plus the words
+/// added to drafts (`draft_days`) on each day.
+pub fn daily_words(history: &[FlowEntry], draft_days: &BTreeMap<NaiveDate, usize>) -> BTreeMap<NaiveDate, usize> {
+    let mut days = draft_days.clone();
+    for entry in history {
+        let day = entry.timestamp.with_timezone(&chrono::Local).date_naive();
+        *days.entry(day).or_insert(0) += count_words(&entry.text);
+    }
+    days
+}
+
+/// Consecutive days reaching `min_words`, ending today. A day that hasn't
+/// been written yet doesn't break the streak until it's over.
+pub fn streak(daily: &BTreeMap<NaiveDate, usize>, min_words: usize, today: NaiveDate) -> usize {
+    let counts = |day: &NaiveDate| daily.get(day).is_some_and(|&w| w > 0 && w >= min_words);
+    let mut day = if counts(&today) {
+        today
+    } else {
+        match today.checked_sub_days(Days::new(1)) {
+            Some(d) => d,
+            None => return 0,
+        }
+    };
+    let mut streak = 0;
+    while counts(&day) {
+        streak += 1;
+        match day.checked_sub_days(Days::new(1)) {
+            Some(d) => day = d,
+            None => break,
+        }
+    }
+    streak
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draft_words_count_towards_the_streak() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 10).unwrap();
+        let yesterday = NaiveDate::from_ymd_opt(2026, 3, 9).unwrap();
+        // No flow sessions at all, only draft saves on both days
+        let drafts = BTreeMap::from([(today, 40), (yesterday, 250)]);
+        let daily = daily_words(&[], &drafts);
+        assert_eq!(streak(&daily, 1, today), 2);
+        // Today hasn't reached the threshold yet, which doesn't break the streak
+        assert_eq!(streak(&daily, 100, today), 1);
+        assert_eq!(streak(&daily, 1000, today), 0);
+    }
+}
@@ -1,8 +1,41 @@
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+// Set when the configured storage path turned out to be unwritable at startup
+static STORAGE_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Settings file given with --config, used instead of the one in the app dir
+static SETTINGS_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Set by --read-only; every write below refuses while it's on
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Set once settings.json has failed to parse. The app is then running on
+/// defaults, so saving would overwrite whatever the user had in there.
+static SETTINGS_UNREADABLE: AtomicBool = AtomicBool::new(false);
+
+/// flow_history.json exists but isn't in a shape that can be read, so
+/// nothing should be saved over it without asking
+#[derive(Debug)]
+pub struct UnreadableHistory(pub String);
+
+impl std::fmt::Display for UnreadableHistory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "flow history can't be read ({})", self.0)
+    }
+}
+
+impl std::error::Error for UnreadableHistory {}
+
+/// Imports larger than this trigger a warning
+pub const IMPORT_WARN_BYTES: u64 = 1024 * 1024;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FlowEntry {
@@ -11,7 +44,242 @@ pub struct FlowEntry {
     pub text: String,
 }
 
+/// In-progress flow session, written periodically so it survives a crash
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FlowRecovery {
+    pub saved_at: DateTime<Utc>,
+    pub duration_secs: u64,
+    pub elapsed_secs: u64,
+    pub text: String,
+}
+
+/// Cached facts about a draft file in draft_index.json, trusted while the
+/// file's size and modification time still match
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct DraftIndexEntry {
+    words: usize,
+    size: u64,
+    modified_ms: u64,
+}
+
+impl DraftIndexEntry {
+    fn matches(&self, metadata: &fs::Metadata) -> bool {
+        self.size == metadata.len() && Some(self.modified_ms) == modified_ms(metadata)
+    }
+}
+
+fn modified_ms(metadata: &fs::Metadata) -> Option<u64> {
+    let modified = metadata.modified().ok()?;
+    Some(modified.duration_since(std::time::UNIX_EPOCH).ok()?.as_millis() as u64)
+}
+
+/// View state remembered per draft in draft_state.json
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct DraftState {
+    /// Last left in preview rather than the editor
+    pub preview: bool,
+}
+
+/// A flow entry without its text, for listing history a page at a time
+#[derive(Debug, Clone)]
+pub struct FlowEntryHeader {
+    /// Position in the history file, for fetching the full entry
+    pub index: usize,
+    pub timestamp: DateTime<Utc>,
+    pub duration_minutes: u32,
+    pub words: usize,
+    pub preview: String,
+}
+
+impl FlowEntryHeader {
+    fn new(entry: &FlowEntry, index: usize) -> Self {
+        Self {
+            index,
+            timestamp: entry.timestamp,
+            duration_minutes: entry.duration_minutes,
+            words: crate::stats::count_words(&entry.text),
+            preview: entry.text.lines().next().unwrap_or("Empty").chars().take(50).collect(),
+        }
+    }
+}
+
+/// How the history view orders sessions
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum HistoryOrder {
+    /// Newest first, as stored
+    #[default]
+    Date,
+    Words,
+    Duration,
+}
+
+/// Reads every history entry one at a time, keeping only its header
+struct HistoryHeaders;
+
+impl<'de> serde::de::Visitor<'de> for HistoryHeaders {
+    type Value = Vec<FlowEntryHeader>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a list of flow entries")
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error> {
+        let mut headers = Vec::new();
+        while let Some(entry) = seq.next_element::<FlowEntry>()? {
+            headers.push(FlowEntryHeader::new(&entry, headers.len()));
+        }
+        Ok(headers)
+    }
+}
+
+/// Reads `limit` history entries starting at `offset` while counting the
+/// rest, so only one page of session text is ever held in memory.
+struct HistoryWindow {
+    offset: usize,
+    limit: usize,
+}
+
+impl<'de> serde::de::Visitor<'de> for HistoryWindow {
+    type Value = (Vec<FlowEntry>, usize);
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a list of flow entries")
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+        loop {
+            let in_window = total >= self.offset && total < self.offset + self.limit;
+            let found = if in_window {
+                seq.next_element::<FlowEntry>()?.map(|e| entries.push(e)).is_some()
+            } else {
+                seq.next_element::<serde::de::IgnoredAny>()?.is_some()
+            };
+            if !found {
+                return Ok((entries, total));
+            }
+            total += 1;
+        }
+    }
+}
+
+/// What the splash screen shows under the title
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SplashContent {
+    #[default]
+    Credits,
+    Quote,
+    Stats,
+}
+
+/// How the time left in a flow session is shown
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowTimerStyle {
+    #[default]
+    Digits,
+    /// A bar along the bottom edge that drains as the session runs
+    Bar,
+    Both,
+}
+
+/// Colors for the whole UI. `Terminal` keeps the terminal's own colors;
+/// `Paper` drops color from the editor altogether
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    #[default]
+    Terminal,
+    Cream,
+    Dusk,
+    Forest,
+    Paper,
+}
+
+/// What a vim leader sequence (e.g. `<Space> w`) runs
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderAction {
+    Save,
+    SaveAs,
+    Preview,
+    SpellCheck,
+    NewDraft,
+    Find,
+    Outline,
+}
+
+/// Reads `leader_mappings`, dropping entries whose action isn't known (one
+/// from a newer version, or a typo) rather than rejecting the whole file
+fn lenient_leader_mappings<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<BTreeMap<String, LeaderAction>, D::Error> {
+    let raw = BTreeMap::<String, serde_json::Value>::deserialize(deserializer)?;
+    Ok(raw
+        .into_iter()
+        .filter_map(|(key, action)| Some((key, serde_json::from_value(action).ok()?)))
+        .collect())
+}
+
+/// Line endings drafts are saved with. `Auto` keeps whatever each file had
+/// when it was opened, using `\n` for new ones.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEndings {
+    #[default]
+    Auto,
+    Lf,
+    Crlf,
+}
+
+impl LineEndings {
+    /// Separator to join lines with, given whether the draft had CRLF endings when opened
+    pub fn separator(self, opened_crlf: bool) -> &'static str {
+        match self {
+            LineEndings::Auto if opened_crlf => "\r\n",
+            LineEndings::Auto | LineEndings::Lf => "\n",
+            LineEndings::Crlf => "\r\n",
+        }
+    }
+}
+
+/// How word and character counts are grouped for display
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberFormat {
+    Plain,
+    #[default]
+    Comma,
+    Period,
+    Space,
+}
+
+impl NumberFormat {
+    /// `1234567` as "1,234,567", "1.234.567", "1 234 567" or unchanged
+    pub fn format(self, n: usize) -> String {
+        let separator = match self {
+            NumberFormat::Plain => return n.to_string(),
+            NumberFormat::Comma => ',',
+            NumberFormat::Period => '.',
+            NumberFormat::Space => ' ',
+        };
+        let digits = n.to_string();
+        let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                out.push(separator);
+            }
+            out.push(c);
+        }
+        out
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Settings {
     pub default_extension: String,
     pub storage_path: String,
@@ -19,6 +287,105 @@ pub struct Settings {
     pub show_splash_screen: bool,
     pub last_seen_version: String,
     pub spellcheck_enabled: bool,
+    /// Skip code and links when checking markdown drafts
+    pub spellcheck_markdown_aware: bool,
+    pub confirm_flow_exit: bool,
+    /// Also write finished flow sessions to the drafts folder
+    pub flow_save_as_draft: bool,
+    /// Line put above flow sessions saved as drafts, e.g. "## {date} — {minutes} min sprint".
+    /// `{date}`, `{time}`, `{minutes}` and `{words}` are filled in. Empty adds nothing.
+    pub flow_entry_header: String,
+    /// Word interval for flow-mode encouragement messages (0 disables)
+    pub flow_milestone_words: usize,
+    /// Dim the flow screen after this many seconds without typing (0 = never)
+    pub flow_idle_dim_secs: usize,
+    /// Flow timer turns `flow_warn_color` below this many seconds
+    pub flow_warn_secs: u64,
+    /// Flow timer turns `flow_caution_color` below this many seconds (0 disables)
+    pub flow_caution_secs: u64,
+    /// Timer colors: names like "green" / "light-red" or "#rrggbb"
+    pub flow_timer_color: String,
+    pub flow_caution_color: String,
+    pub flow_warn_color: String,
+    pub flow_overtime_color: String,
+    /// End and save flow sessions when the timer runs out; when off the
+    /// session continues into overtime until ended with Esc
+    pub flow_auto_end: bool,
+    /// Custom Writing status bar, e.g. "{words} | {mode} | {time} | {dirty}".
+    /// Empty uses the built-in layout.
+    pub status_template: String,
+    /// Number of previous versions to keep per draft (0 disables snapshots)
+    pub keep_versions: usize,
+    pub splash_content: SplashContent,
+    /// Optional file of splash quotes, one per line
+    pub splash_quotes_file: String,
+    /// Starting text for new drafts: a path to a template file or the text itself.
+    /// `{date}` is filled in, and `{title}` once the draft is first saved.
+    /// Empty starts with a blank buffer.
+    pub new_draft_template: String,
+    /// Words (from flow sessions and draft saves) needed on a day for it to
+    /// count towards the streak
+    pub streak_min_words: usize,
+    /// Thousands separators for word and character counts
+    pub number_format: NumberFormat,
+    /// Use the terminal's own cursor in the editor: a bar while typing and a
+    /// block in vim Normal/Visual mode
+    pub terminal_cursor: bool,
+    /// Blink the terminal cursor (only used with `terminal_cursor`)
+    pub cursor_blink: bool,
+    /// Save the flow session or unsaved drafts when killed (SIGTERM/SIGHUP)
+    pub save_on_signal: bool,
+    /// Flow sessions kept in flow_history.json before older ones are moved
+    /// to yearly archives (0 keeps everything)
+    pub max_history_entries: usize,
+    /// Ask before quitting from the menu (always asked with unsaved changes)
+    pub confirm_quit: bool,
+    /// Ask before a rename replaces an existing draft; off picks a free
+    /// `name-N` instead
+    pub confirm_rename_overwrite: bool,
+    /// Columns a tab advances to in the editor and preview
+    pub tab_width: usize,
+    /// Replace tabs with spaces (at `tab_width`) when saving a draft
+    pub expand_tabs_on_save: bool,
+    /// Line endings drafts are written with; they're always `\n` while being edited
+    pub line_endings: LineEndings,
+    /// Lock the screen after this many seconds without a key press (0 = never)
+    pub idle_lock_secs: usize,
+    /// Needed to unlock; stored as typed, so it only guards against passers-by
+    pub lock_passphrase: String,
+    /// Append the words written to a CSV log on every save and flow session
+    pub word_log: bool,
+    /// Where the word log goes; empty means word_log.csv in the storage folder
+    pub word_log_path: String,
+    /// Seconds between saves of a running flow session to flow_recovery.json
+    /// (0 turns crash recovery off)
+    pub flow_recovery_secs: usize,
+    /// Print what was written this session to the terminal after quitting
+    pub print_summary: bool,
+    /// Live words-per-minute meter in the status bar and flow screen
+    pub show_wpm: bool,
+    /// Draw an extra blank row after each paragraph break in the editor,
+    /// without touching the text
+    pub visual_paragraph_spacing: bool,
+    /// Dim bar in the editor at the column where hard wrap breaks lines
+    pub wrap_guide: bool,
+    /// Length of the last `writeapp flow --time` session, used by the menu's `f`
+    pub last_flow_minutes: u64,
+    /// Leave the splash after 30 seconds or on any key; when off it stays
+    /// until Enter or Space
+    pub splash_auto_advance: bool,
+    /// Digits, a draining bar or both for the time left in a flow session
+    pub flow_timer_style: FlowTimerStyle,
+    /// Starts a leader sequence in vim Normal mode
+    pub leader_key: char,
+    /// Key pressed after the leader -> action to run
+    #[serde(deserialize_with = "lenient_leader_mappings")]
+    pub leader_mappings: BTreeMap<String, LeaderAction>,
+    /// Tab types spaces up to the next tab stop instead of a tab character
+    pub soft_tabs: bool,
+    /// Drafts bigger than this many KB open read-only; 0 for no limit
+    pub max_draft_kb: usize,
+    pub theme: ThemeName,
 }
 
 impl Default for Settings {
@@ -40,17 +407,117 @@ impl Default for Settings {
             show_splash_screen: true,
             last_seen_version: String::new(),
             spellcheck_enabled: true,
+            spellcheck_markdown_aware: true,
+            confirm_flow_exit: true,
+            flow_save_as_draft: false,
+            flow_entry_header: String::new(),
+            flow_milestone_words: 100,
+            flow_idle_dim_secs: 0,
+            flow_warn_secs: 60,
+            flow_caution_secs: 0,
+            flow_timer_color: "green".to_string(),
+            flow_caution_color: "yellow".to_string(),
+            flow_warn_color: "red".to_string(),
+            flow_overtime_color: "magenta".to_string(),
+            flow_auto_end: true,
+            status_template: String::new(),
+            keep_versions: 0,
+            splash_content: SplashContent::Credits,
+            splash_quotes_file: String::new(),
+            new_draft_template: String::new(),
+            streak_min_words: 1,
+            number_format: NumberFormat::Comma,
+            terminal_cursor: false,
+            cursor_blink: true,
+            save_on_signal: true,
+            max_history_entries: 0,
+            confirm_quit: false,
+            confirm_rename_overwrite: true,
+            tab_width: 4,
+            expand_tabs_on_save: false,
+            line_endings: LineEndings::Auto,
+            idle_lock_secs: 0,
+            lock_passphrase: String::new(),
+            word_log: false,
+            word_log_path: String::new(),
+            flow_recovery_secs: 5,
+            print_summary: false,
+            show_wpm: false,
+            visual_paragraph_spacing: false,
+            wrap_guide: false,
+            last_flow_minutes: 10,
+            splash_auto_advance: true,
+            flow_timer_style: FlowTimerStyle::Digits,
+            leader_key: ' ',
+            leader_mappings: BTreeMap::from([
+                ("w".to_string(), LeaderAction::Save),
+                ("W".to_string(), LeaderAction::SaveAs),
+                ("p".to_string(), LeaderAction::Preview),
+                ("s".to_string(), LeaderAction::SpellCheck),
+                ("n".to_string(), LeaderAction::NewDraft),
+                ("f".to_string(), LeaderAction::Find),
+                ("o".to_string(), LeaderAction::Outline),
+            ]),
+            soft_tabs: true,
+            max_draft_kb: 2048,
+            theme: ThemeName::Terminal,
         }
     }
 }
 
+impl Settings {
+    /// The settings.json fields whose values differ from their defaults
+    pub fn customized_fields(&self) -> BTreeSet<String> {
+        let current = serde_json::to_value(self).unwrap_or_default();
+        let default = serde_json::to_value(Settings::default()).unwrap_or_default();
+        match current.as_object() {
+            Some(fields) => fields
+                .iter()
+                .filter(|(field, value)| default.get(field.as_str()) != Some(*value))
+                .map(|(field, _)| field.clone())
+                .collect(),
+            None => BTreeSet::new(),
+        }
+    }
+
+    /// Whether the setting stored under `field` is a whole number
+    pub fn is_number(&self, field: &str) -> bool {
+        serde_json::to_value(self).ok().and_then(|v| v.get(field).map(|v| v.is_u64())).unwrap_or(false)
+    }
+
+    /// Sets the number setting stored under `field`, failing if it isn't one
+    /// or `value` is too big for it
+    pub fn set_number(&mut self, field: &str, value: u64) -> Result<()> {
+        if !self.is_number(field) {
+            anyhow::bail!("{} isn't a number", field);
+        }
+        let mut current = serde_json::to_value(&*self)?;
+        current[field] = value.into();
+        *self = serde_json::from_value(current)?;
+        Ok(())
+    }
+
+    /// Puts the setting stored under `field` back to its default
+    pub fn reset_field(&mut self, field: &str) -> Result<()> {
+        let mut current = serde_json::to_value(&*self)?;
+        let default = serde_json::to_value(Settings::default())?;
+        if let (Some(slot), Some(value)) = (current.get_mut(field), default.get(field)) {
+            *slot = value.clone();
+        }
+        *self = serde_json::from_value(current)?;
+        Ok(())
+    }
+}
+
 pub struct Storage;
 
 impl Storage {
+    /// The config directory, created if missing unless in read-only mode
+    /// (anything read from it then simply isn't there)
     fn get_app_dir() -> Result<PathBuf> {
         if let Some(proj_dirs) = directories::ProjectDirs::from("com", "writeapp", "writeapp") {
             let config_dir = proj_dirs.config_dir();
-            if !config_dir.exists() {
+            if !config_dir.exists() && !Self::is_read_only() {
                 fs::create_dir_all(config_dir)?;
             }
             Ok(config_dir.to_path_buf())
@@ -59,7 +526,7 @@ impl Storage {
             let home = directories::UserDirs::new()
                 .ok_or_else(|| anyhow::anyhow!("Could not find user home directory"))?;
             let path = home.home_dir().join(".config").join("writeapp");
-            if !path.exists() {
+            if !path.exists() && !Self::is_read_only() {
                 fs::create_dir_all(&path)?;
             }
             Ok(path)
@@ -67,73 +534,635 @@ impl Storage {
     }
 
     fn get_content_dir() -> Result<PathBuf> {
-        let settings = Self::load_settings()?;
-        let path = PathBuf::from(settings.storage_path);
-        if !path.exists() {
+        let overridden = STORAGE_OVERRIDE.lock().ok().and_then(|o| o.clone());
+        let path = match overridden {
+            Some(path) => path,
+            None => PathBuf::from(Self::load_settings().unwrap_or_default().storage_path),
+        };
+        if !path.exists() && !Self::is_read_only() {
             fs::create_dir_all(&path)?;
         }
         Ok(path)
     }
 
+    /// Checks that the storage directory can be written to by creating and
+    /// removing a probe file.
+    fn probe_writable(path: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(path)?;
+        let probe = path.join(".writeapp_write_test");
+        fs::write(&probe, b"")?;
+        fs::remove_file(probe)
+    }
+
+    /// Verifies the configured storage directory at startup. If it can't be
+    /// written to, switches this session to the first writable fallback and
+    /// returns a warning describing what happened.
+    pub fn verify_storage() -> Option<String> {
+        if Self::is_read_only() {
+            return Some("Read-only mode: nothing will be saved this session".to_string());
+        }
+        let configured = PathBuf::from(Self::load_settings().unwrap_or_default().storage_path);
+        let err = match Self::probe_writable(&configured) {
+            Ok(()) => return None,
+            Err(e) => e,
+        };
+
+        let mut fallbacks = vec![PathBuf::from(Settings::default().storage_path)];
+        if let Ok(app_dir) = Self::get_app_dir() {
+            fallbacks.push(app_dir.join("data"));
+        }
+        for fallback in fallbacks.into_iter().filter(|p| *p != configured) {
+            if Self::probe_writable(&fallback).is_ok() {
+                let msg = format!(
+                    "Storage {} is not writable ({:?}); using {} for this session",
+                    configured.display(), err.kind(), fallback.display()
+                );
+                if let Ok(mut o) = STORAGE_OVERRIDE.lock() {
+                    *o = Some(fallback);
+                }
+                return Some(msg);
+            }
+        }
+        Some(format!(
+            "Storage {} is not writable ({:?}) and no fallback was found; saving will fail",
+            configured.display(), err.kind()
+        ))
+    }
+
     fn get_history_path() -> Result<PathBuf> {
         let dir = Self::get_content_dir()?;
         Ok(dir.join("flow_history.json"))
     }
 
+    fn get_recovery_path() -> Result<PathBuf> {
+        let dir = Self::get_content_dir()?;
+        Ok(dir.join("flow_recovery.json"))
+    }
+
+    fn get_draft_state_path() -> Result<PathBuf> {
+        let dir = Self::get_content_dir()?;
+        Ok(dir.join("draft_state.json"))
+    }
+
+    fn get_draft_days_path() -> Result<PathBuf> {
+        let dir = Self::get_content_dir()?;
+        Ok(dir.join("draft_days.json"))
+    }
+
+    /// Words added to drafts per local calendar day, from saves
+    pub fn load_draft_days() -> BTreeMap<NaiveDate, usize> {
+        Self::get_draft_days_path()
+            .and_then(|path| Ok(fs::read_to_string(path)?))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Adds words written in a draft to today's total
+    pub fn record_draft_words(words: usize) -> Result<()> {
+        Self::check_writable()?;
+        let mut days = Self::load_draft_days();
+        *days.entry(chrono::Local::now().date_naive()).or_insert(0) += words;
+        fs::write(Self::get_draft_days_path()?, serde_json::to_string_pretty(&days)?)?;
+        Ok(())
+    }
+
+    /// Keyed by full path so switching storage folders can't mix up counts
+    fn get_draft_index_path() -> Result<PathBuf> {
+        Ok(Self::get_app_dir()?.join("draft_index.json"))
+    }
+
+    fn load_draft_index() -> BTreeMap<String, DraftIndexEntry> {
+        Self::get_draft_index_path()
+            .and_then(|path| Ok(fs::read_to_string(path)?))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_draft_index(index: &BTreeMap<String, DraftIndexEntry>) -> Result<()> {
+        Self::check_writable()?;
+        fs::write(Self::get_draft_index_path()?, serde_json::to_string(index)?)?;
+        Ok(())
+    }
+
+    /// Word counts for the given drafts. Counts come from the index and only
+    /// drafts changed since they were indexed are read again.
+    pub fn draft_word_counts(names: &[String]) -> BTreeMap<String, usize> {
+        let Ok(dir) = Self::get_content_dir().map(|d| d.join("drafts")) else {
+            return BTreeMap::new();
+        };
+        let mut index = Self::load_draft_index();
+        let mut changed = false;
+        let mut counts = BTreeMap::new();
+        for name in names {
+            let path = dir.join(name);
+            let Ok(metadata) = fs::metadata(&path) else { continue };
+            let key = path.to_string_lossy().to_string();
+            let words = match index.get(&key) {
+                Some(entry) if entry.matches(&metadata) => entry.words,
+                _ => {
+                    let Ok(content) = fs::read_to_string(&path) else { continue };
+                    let words = crate::stats::count_words(&content);
+                    if let Some(modified_ms) = modified_ms(&metadata) {
+                        index.insert(key, DraftIndexEntry { words, size: metadata.len(), modified_ms });
+                        changed = true;
+                    }
+                    words
+                }
+            };
+            counts.insert(name.clone(), words);
+        }
+        // Renamed and deleted drafts in this folder drop out of the index
+        let before = index.len();
+        index.retain(|key, _| !Path::new(key).starts_with(&dir) || Path::new(key).exists());
+        changed |= index.len() != before;
+        if changed {
+            // Best effort: the counts are still right without the cache
+            let _ = Self::save_draft_index(&index);
+        }
+        counts
+    }
+
+    fn load_draft_states() -> Result<BTreeMap<String, DraftState>> {
+        let path = Self::get_draft_state_path()?;
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = fs::read_to_string(path)?;
+        // An error rather than an empty map, so a damaged file isn't saved over
+        serde_json::from_str(&content).map_err(|e| anyhow::anyhow!("draft_state.json can't be read ({})", e))
+    }
+
+    fn save_draft_states(states: &BTreeMap<String, DraftState>) -> Result<()> {
+        let path = Self::get_draft_state_path()?;
+        fs::write(path, serde_json::to_string_pretty(states)?)?;
+        Ok(())
+    }
+
+    pub fn load_draft_state(filename: &str) -> Result<DraftState> {
+        Ok(Self::load_draft_states()?.remove(filename).unwrap_or_default())
+    }
+
+    pub fn save_draft_state(filename: &str, state: DraftState) -> Result<()> {
+        Self::check_writable()?;
+        let mut states = Self::load_draft_states()?;
+        states.insert(filename.to_string(), state);
+        Self::save_draft_states(&states)
+    }
+
+    /// Keeps remembered state with a draft through renames and deletes
+    /// (`new_name` of `None` forgets it)
+    /// An unreadable draft_state.json is left as it is rather than holding
+    /// up the rename or delete.
+    fn move_draft_state(old_name: &str, new_name: Option<&str>) -> Result<()> {
+        let Ok(mut states) = Self::load_draft_states() else {
+            return Ok(());
+        };
+        if let Some(state) = states.remove(old_name) {
+            if let Some(new_name) = new_name {
+                states.insert(new_name.to_string(), state);
+            }
+            Self::save_draft_states(&states)?;
+        }
+        Ok(())
+    }
+
+    fn get_snippets_path() -> Result<PathBuf> {
+        let dir = Self::get_content_dir()?;
+        Ok(dir.join("snippets.json"))
+    }
+
+    /// User snippets as trigger -> expansion. A missing file means no snippets.
+    pub fn load_snippets() -> Result<BTreeMap<String, String>> {
+        let path = Self::get_snippets_path()?;
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn set_read_only(on: bool) {
+        READ_ONLY.store(on, Ordering::Relaxed);
+    }
+
+    pub fn is_read_only() -> bool {
+        READ_ONLY.load(Ordering::Relaxed)
+    }
+
+    fn check_writable() -> Result<()> {
+        if Self::is_read_only() {
+            anyhow::bail!("read-only mode");
+        }
+        Ok(())
+    }
+
     fn get_settings_path() -> Result<PathBuf> {
+        if let Some(path) = SETTINGS_OVERRIDE.lock().ok().and_then(|o| o.clone()) {
+            return Ok(path);
+        }
         let dir = Self::get_app_dir()?;
         Ok(dir.join("settings.json"))
     }
 
+    /// Uses `path` as the settings file for this run, writing the defaults
+    /// there if it doesn't exist yet.
+    pub fn use_settings_file(path: &Path) -> Result<()> {
+        let path = std::path::absolute(path)?;
+        if !path.exists() && !Self::is_read_only() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, serde_json::to_string_pretty(&Settings::default())?)?;
+        }
+        if let Ok(mut o) = SETTINGS_OVERRIDE.lock() {
+            *o = Some(path);
+        }
+        Ok(())
+    }
+
+    /// Loads settings. A missing file means defaults; a file that can't be
+    /// read or parsed is an error so the caller can tell the user.
     pub fn load_settings() -> Result<Settings> {
         let path = Self::get_settings_path()?;
         if !path.exists() {
             return Ok(Settings::default());
         }
-        let content = fs::read_to_string(path)?;
-        let settings: Settings = serde_json::from_str(&content).unwrap_or_default();
+        let content = fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        let settings: Settings = serde_json::from_str(&content).map_err(|e| {
+            SETTINGS_UNREADABLE.store(true, Ordering::Relaxed);
+            anyhow::anyhow!("{}: {}", path.display(), e)
+        })?;
         Ok(settings)
     }
 
+    /// Whether settings.json failed to parse and hasn't been set aside yet
+    pub fn settings_unreadable() -> bool {
+        SETTINGS_UNREADABLE.load(Ordering::Relaxed)
+    }
+
+    /// Moves an unreadable settings.json out of the way so settings can be
+    /// saved again. Returns where it went.
+    pub fn set_aside_settings() -> Result<PathBuf> {
+        Self::check_writable()?;
+        let path = Self::get_settings_path()?;
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        let aside = path.with_file_name(format!("settings.unreadable-{}.json", stamp));
+        fs::rename(&path, &aside)?;
+        SETTINGS_UNREADABLE.store(false, Ordering::Relaxed);
+        Ok(aside)
+    }
+
+    /// The `key -> action` leader mappings in settings.json that loading
+    /// skipped because the action isn't one this version knows
+    pub fn unknown_leader_mappings() -> Vec<String> {
+        let Some(value) = Self::get_settings_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        else {
+            return Vec::new();
+        };
+        let Some(mappings) = value.get("leader_mappings").and_then(|m| m.as_object()) else {
+            return Vec::new();
+        };
+        mappings
+            .iter()
+            .filter(|(_, action)| serde_json::from_value::<LeaderAction>((*action).clone()).is_err())
+            .map(|(key, action)| format!("{} -> {}", key, action))
+            .collect()
+    }
+
     pub fn save_settings(settings: &Settings) -> Result<()> {
+        Self::check_writable()?;
+        if Self::settings_unreadable() {
+            anyhow::bail!("settings.json can't be read, so it isn't saved over");
+        }
         let path = Self::get_settings_path()?;
         let content = serde_json::to_string_pretty(settings)?;
         fs::write(path, content)?;
         Ok(())
     }
 
+    /// The history as saved, falling back to the copy kept from the last
+    /// good save when the file can't be read
     pub fn load_flow_history() -> Result<Vec<FlowEntry>> {
+        Self::read_flow_history().or_else(|e| {
+            let backup = Self::get_history_backup_path()?;
+            if !backup.exists() {
+                return Err(e);
+            }
+            Ok(serde_json::from_str(&fs::read_to_string(backup)?)?)
+        })
+    }
+
+    /// Reads flow_history.json, also accepting a lone entry in place of the
+    /// array. Anything else is an `UnreadableHistory` error.
+    fn read_flow_history() -> Result<Vec<FlowEntry>> {
         let path = Self::get_history_path()?;
         if !path.exists() {
             return Ok(Vec::new());
         }
-        let content = fs::read_to_string(path)?;
-        let history: Vec<FlowEntry> = serde_json::from_str(&content).unwrap_or_default();
-        Ok(history)
+        let content = fs::read_to_string(&path)?;
+        match serde_json::from_str::<Vec<FlowEntry>>(&content) {
+            Ok(history) => Ok(history),
+            Err(e) => match serde_json::from_str::<FlowEntry>(&content) {
+                Ok(entry) => Ok(vec![entry]),
+                Err(_) => Err(UnreadableHistory(format!("{}: {}", path.display(), e)).into()),
+            },
+        }
+    }
+
+    fn get_history_backup_path() -> Result<PathBuf> {
+        let dir = Self::get_content_dir()?;
+        Ok(dir.join("flow_history.json.bak"))
     }
 
-    pub fn save_flow_entry(entry: FlowEntry) -> Result<()> {
-        let mut history = Self::load_flow_history()?;
+    /// Moves an unreadable flow_history.json out of the way, putting the
+    /// backup from the last good save in its place. Returns where it went.
+    pub fn set_aside_flow_history() -> Result<PathBuf> {
+        Self::check_writable()?;
+        let path = Self::get_history_path()?;
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        let aside = path.with_file_name(format!("flow_history.unreadable-{}.json", stamp));
+        fs::rename(&path, &aside)?;
+        let backup = Self::get_history_backup_path()?;
+        if backup.exists() {
+            fs::copy(backup, &path)?;
+        }
+        Ok(aside)
+    }
+
+    /// Adds a session to the history. With `max_entries` set, the oldest
+    /// entries beyond it move to yearly archive files. Refuses to write over
+    /// a history file it can't read.
+    pub fn save_flow_entry(entry: FlowEntry, max_entries: usize) -> Result<()> {
+        Self::check_writable()?;
+        let mut history = Self::read_flow_history()?;
         history.push(entry);
         // Sort by timestamp descending
-        history.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        history.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+
+        if max_entries > 0 && history.len() > max_entries {
+            // Archive first so a failure can't lose the entries
+            let oldest = history.split_off(max_entries);
+            Self::archive_flow_entries(oldest)?;
+        }
         
         let path = Self::get_history_path()?;
+        // Read fine above, so worth keeping should the next write go wrong
+        if path.exists() {
+            fs::copy(&path, Self::get_history_backup_path()?)?;
+        }
         let content = serde_json::to_string_pretty(&history)?;
         fs::write(path, content)?;
         Ok(())
     }
 
+    fn get_archive_path(year: i32) -> Result<PathBuf> {
+        let dir = Self::get_content_dir()?;
+        Ok(dir.join(format!("flow_history_{}.json", year)))
+    }
+
+    fn archive_flow_entries(entries: Vec<FlowEntry>) -> Result<()> {
+        let mut by_year: BTreeMap<i32, Vec<FlowEntry>> = BTreeMap::new();
+        for entry in entries {
+            by_year.entry(entry.timestamp.year()).or_default().push(entry);
+        }
+        for (year, entries) in by_year {
+            let mut archive = Self::load_flow_archive(year)?;
+            archive.extend(entries);
+            archive.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+            let content = serde_json::to_string_pretty(&archive)?;
+            fs::write(Self::get_archive_path(year)?, content)?;
+        }
+        Ok(())
+    }
+
+    /// Years that have an archived history file, newest first
+    pub fn list_flow_archives() -> Result<Vec<i32>> {
+        let dir = Self::get_content_dir()?;
+        let mut years: Vec<i32> = fs::read_dir(dir)?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().to_string();
+                name.strip_prefix("flow_history_")?.strip_suffix(".json")?.parse().ok()
+            })
+            .collect();
+        years.sort_by_key(|&y| std::cmp::Reverse(y));
+        Ok(years)
+    }
+
+    pub fn load_flow_archive(year: i32) -> Result<Vec<FlowEntry>> {
+        let path = Self::get_archive_path(year)?;
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn history_file(archive: Option<i32>) -> Result<PathBuf> {
+        match archive {
+            Some(year) => Self::get_archive_path(year),
+            None => Self::get_history_path(),
+        }
+    }
+
+    fn read_history_window(archive: Option<i32>, offset: usize, limit: usize) -> Result<(Vec<FlowEntry>, usize)> {
+        let path = Self::history_file(archive)?;
+        if !path.exists() {
+            return Ok((Vec::new(), 0));
+        }
+        let reader = std::io::BufReader::new(fs::File::open(path)?);
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        Ok(serde::Deserializer::deserialize_seq(&mut de, HistoryWindow { offset, limit })?)
+    }
+
+    /// One page of the live history (or a year's archive) and the total entry count
+    pub fn load_flow_history_page(archive: Option<i32>, offset: usize, limit: usize) -> Result<(Vec<FlowEntryHeader>, usize)> {
+        let (entries, total) = Self::read_history_window(archive, offset, limit)?;
+        let headers = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| FlowEntryHeader::new(e, offset + i))
+            .collect();
+        Ok((headers, total))
+    }
+
+    /// Like `load_flow_history_page`, but in `order` and optionally without
+    /// sessions that have no words. Sorting needs every entry, so all of them
+    /// are read, but only their headers are kept.
+    pub fn load_flow_history_sorted_page(
+        archive: Option<i32>,
+        order: HistoryOrder,
+        hide_empty: bool,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<FlowEntryHeader>, usize)> {
+        if order == HistoryOrder::Date && !hide_empty {
+            return Self::load_flow_history_page(archive, offset, limit);
+        }
+        let path = Self::history_file(archive)?;
+        if !path.exists() {
+            return Ok((Vec::new(), 0));
+        }
+        let reader = std::io::BufReader::new(fs::File::open(path)?);
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        let mut headers = serde::Deserializer::deserialize_seq(&mut de, HistoryHeaders)?;
+        if hide_empty {
+            headers.retain(|h| h.words > 0);
+        }
+        // Stable sorts, so ties stay newest first
+        match order {
+            HistoryOrder::Date => {}
+            HistoryOrder::Words => headers.sort_by_key(|h| std::cmp::Reverse(h.words)),
+            HistoryOrder::Duration => headers.sort_by_key(|h| std::cmp::Reverse(h.duration_minutes)),
+        }
+        let total = headers.len();
+        Ok((headers.into_iter().skip(offset).take(limit).collect(), total))
+    }
+
+    pub fn load_flow_history_entry(archive: Option<i32>, index: usize) -> Result<Option<FlowEntry>> {
+        let (mut entries, _) = Self::read_history_window(archive, index, 1)?;
+        Ok(entries.pop())
+    }
+
+    pub fn save_flow_recovery(recovery: &FlowRecovery) -> Result<()> {
+        Self::check_writable()?;
+        let path = Self::get_recovery_path()?;
+        let content = serde_json::to_string(recovery)?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn load_flow_recovery() -> Result<Option<FlowRecovery>> {
+        let path = Self::get_recovery_path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content).ok())
+    }
+
+    pub fn clear_flow_recovery() -> Result<()> {
+        Self::check_writable()?;
+        let path = Self::get_recovery_path()?;
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Appends `date,draft,words,total` to the word log, where `words` is the
+    /// change since the last line logged for `draft`. Saves that didn't change
+    /// the count aren't logged.
+    pub fn append_word_log(log_path: &str, draft: &str, total: usize) -> Result<()> {
+        Self::check_writable()?;
+        let path = if log_path.is_empty() {
+            Self::get_content_dir()?.join("word_log.csv")
+        } else {
+            PathBuf::from(log_path)
+        };
+        let existing = if path.exists() { fs::read_to_string(&path)? } else { String::new() };
+
+        let field = if draft.contains([',', '"']) {
+            format!("\"{}\"", draft.replace('"', "\"\""))
+        } else {
+            draft.to_string()
+        };
+        // The draft sits between the date and the two numeric columns, so
+        // splitting from both ends leaves it intact even when quoted
+        let previous = existing
+            .lines()
+            .rev()
+            .filter_map(|line| {
+                let (rest, total) = line.rsplit_once(',')?;
+                let (rest, _) = rest.rsplit_once(',')?;
+                let (_, name) = rest.split_once(',')?;
+                (name == field).then(|| total.trim().parse::<usize>().ok()).flatten()
+            })
+            .next()
+            .unwrap_or(0);
+        let written = total as i64 - previous as i64;
+        if written == 0 {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+        if existing.is_empty() {
+            writeln!(file, "date,draft,words,total")?;
+        }
+        writeln!(file, "{},{},{},{}", chrono::Local::now().format("%Y-%m-%d"), field, written, total)?;
+        Ok(())
+    }
+
     pub fn save_draft(filename: &str, content: &str) -> Result<()> {
+        Self::check_writable()?;
         let dir = Self::get_content_dir()?.join("drafts");
         if !dir.exists() {
             fs::create_dir_all(&dir)?;
         }
         let path = dir.join(filename);
-        fs::write(path, content)?;
+        let keep_versions = Self::load_settings().unwrap_or_default().keep_versions;
+        if keep_versions > 0 && path.exists() {
+            Self::snapshot_draft(&dir, filename, keep_versions)?;
+        }
+        // The drafts list re-counts it on its next look, when the file no
+        // longer matches its index entry
+        fs::write(&path, content)?;
         Ok(())
     }
 
+    fn get_versions_dir(filename: &str) -> Result<PathBuf> {
+        Ok(Self::get_content_dir()?.join("drafts").join(".versions").join(filename))
+    }
+
+    /// Copies the current file into `drafts/.versions/<name>/<timestamp>` and
+    /// prunes the oldest snapshots beyond `keep`.
+    fn snapshot_draft(drafts_dir: &Path, filename: &str, keep: usize) -> Result<()> {
+        let versions_dir = Self::get_versions_dir(filename)?;
+        if !versions_dir.exists() {
+            fs::create_dir_all(&versions_dir)?;
+        }
+        let stamp = Utc::now().format("%Y%m%d-%H%M%S%.3f").to_string();
+        fs::copy(drafts_dir.join(filename), versions_dir.join(stamp))?;
+
+        let versions = Self::list_versions(filename)?;
+        for old in versions.iter().skip(keep) {
+            fs::remove_file(versions_dir.join(old))?;
+        }
+        Ok(())
+    }
+
+    /// Snapshot names for a draft, newest first
+    pub fn list_versions(filename: &str) -> Result<Vec<String>> {
+        let dir = Self::get_versions_dir(filename)?;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut versions = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.path().is_file() {
+                versions.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        versions.sort_by(|a, b| b.cmp(a));
+        Ok(versions)
+    }
+
+    pub fn load_version(filename: &str, version: &str) -> Result<String> {
+        let path = Self::get_versions_dir(filename)?.join(version);
+        let content = fs::read_to_string(path)?;
+        Ok(content)
+    }
+
     pub fn list_drafts() -> Result<Vec<String>> {
         let dir = Self::get_content_dir()?.join("drafts");
         if !dir.exists() {
@@ -153,27 +1182,225 @@ impl Storage {
         Ok(drafts)
     }
 
+    /// Drafts ordered by modification time, most recent first
+    pub fn list_recent_drafts(limit: usize) -> Result<Vec<String>> {
+        let dir = Self::get_content_dir()?.join("drafts");
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut drafts = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                drafts.push((metadata.modified()?, entry.file_name().to_string_lossy().to_string()));
+            }
+        }
+        drafts.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        Ok(drafts.into_iter().take(limit).map(|(_, name)| name).collect())
+    }
+
+    /// Reads a draft without a leading byte order mark and with every line
+    /// ending as `\n`, however the file was written
     pub fn load_draft(filename: &str) -> Result<String> {
+        Ok(Self::read_draft(filename)?.0)
+    }
+
+    /// Like `load_draft`, also telling whether the file used CRLF line endings
+    pub fn read_draft(filename: &str) -> Result<(String, bool)> {
         let dir = Self::get_content_dir()?.join("drafts");
         let path = dir.join(filename);
-        let content = fs::read_to_string(path)?;
-        Ok(content)
+        Ok(normalize_draft(&fs::read_to_string(path)?))
+    }
+
+    pub fn draft_exists(filename: &str) -> Result<bool> {
+        Ok(Self::get_content_dir()?.join("drafts").join(filename).exists())
+    }
+
+    /// Returns `filename`, or the first free `name-N.ext` variant if a draft
+    /// with that name already exists.
+    pub fn unique_draft_name(filename: &str) -> Result<String> {
+        if !Self::draft_exists(filename)? {
+            return Ok(filename.to_string());
+        }
+        let (stem, ext) = match filename.rsplit_once('.') {
+            Some((stem, ext)) => (stem, format!(".{}", ext)),
+            None => (filename, String::new()),
+        };
+        let mut n = 1;
+        loop {
+            let candidate = format!("{}-{}{}", stem, n, ext);
+            if !Self::draft_exists(&candidate)? {
+                return Ok(candidate);
+            }
+            n += 1;
+        }
     }
 
-    pub fn rename_draft(old_name: &str, new_name: &str) -> Result<()> {
+    /// Renames a draft, taking its view state and saved versions along. Once
+    /// the file itself has moved the rename stands: a failure to move those
+    /// comes back as `Some(warning)` rather than an error.
+    pub fn rename_draft(old_name: &str, new_name: &str) -> Result<Option<String>> {
+        Self::check_writable()?;
         let dir = Self::get_content_dir()?.join("drafts");
         let old_path = dir.join(old_name);
         let new_path = dir.join(new_name);
+        // fs::rename silently replaces an existing target
+        if old_name != new_name && new_path.exists() {
+            anyhow::bail!("a draft named {} already exists", new_name);
+        }
         fs::rename(old_path, new_path)?;
-        Ok(())
+        Ok(Self::follow_rename(old_name, new_name))
+    }
+
+    /// Renames a draft onto `new_name`, replacing any draft already there.
+    /// The replaced draft is snapshotted first when versions are kept.
+    pub fn rename_draft_over(old_name: &str, new_name: &str) -> Result<Option<String>> {
+        Self::check_writable()?;
+        let dir = Self::get_content_dir()?.join("drafts");
+        let new_path = dir.join(new_name);
+        if old_name != new_name && new_path.exists() {
+            let keep_versions = Self::load_settings().unwrap_or_default().keep_versions;
+            if keep_versions > 0 {
+                Self::snapshot_draft(&dir, new_name, keep_versions)?;
+            }
+            Self::move_draft_state(new_name, None)?;
+        }
+        fs::rename(dir.join(old_name), new_path)?;
+        Ok(Self::follow_rename(old_name, new_name))
+    }
+
+    /// Moves a renamed draft's view state and versions after it
+    fn follow_rename(old_name: &str, new_name: &str) -> Option<String> {
+        Self::move_draft_state(old_name, Some(new_name))
+            .and_then(|()| Self::move_versions(old_name, Some(new_name)))
+            .err()
+            .map(|e| format!("its view state or saved versions stayed under the old name ({})", e))
+    }
+
+    /// Reads an arbitrary text file for insertion into the editor. Returns the
+    /// contents and the file size in bytes. A path of `-` reads stdin.
+    pub fn read_import(path: &str) -> Result<(String, u64)> {
+        if path == "-" {
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut content)?;
+            let size = content.len() as u64;
+            return Ok((content, size));
+        }
+        let path = Self::expand_home(path);
+        let metadata = fs::metadata(&path)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        if !metadata.is_file() {
+            anyhow::bail!("{} is not a file", path.display());
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok((content, metadata.len()))
+    }
+
+    /// Text of the new-draft template, read from disk when it names an existing file
+    pub fn load_template(template: &str) -> Result<String> {
+        let path = Self::expand_home(template);
+        if path.is_file() {
+            Ok(fs::read_to_string(path)?)
+        } else {
+            Ok(template.to_string())
+        }
+    }
+
+    fn expand_home(path: &str) -> PathBuf {
+        if let Some(rest) = path.strip_prefix("~/") {
+            if let Some(user_dirs) = directories::UserDirs::new() {
+                return user_dirs.home_dir().join(rest);
+            }
+        }
+        Path::new(path).to_path_buf()
     }
 
     pub fn delete_draft(filename: &str) -> Result<()> {
+        Self::check_writable()?;
         let dir = Self::get_content_dir()?.join("drafts");
         let path = dir.join(filename);
         if path.exists() {
             fs::remove_file(path)?;
         }
+        Self::move_draft_state(filename, None)?;
+        Self::move_versions(filename, None)?;
         Ok(())
     }
+
+    /// Keeps a draft's saved versions with it through renames and deletes
+    /// (`new_name` of `None` removes them). Versions already kept under
+    /// `new_name` stay alongside the moved ones.
+    fn move_versions(old_name: &str, new_name: Option<&str>) -> Result<()> {
+        let old_dir = Self::get_versions_dir(old_name)?;
+        if !old_dir.exists() || Some(old_name) == new_name {
+            return Ok(());
+        }
+        let Some(new_name) = new_name else {
+            fs::remove_dir_all(old_dir)?;
+            return Ok(());
+        };
+        let new_dir = Self::get_versions_dir(new_name)?;
+        if !new_dir.exists() {
+            fs::rename(old_dir, new_dir)?;
+            return Ok(());
+        }
+        for entry in fs::read_dir(&old_dir)? {
+            let entry = entry?;
+            fs::rename(entry.path(), new_dir.join(entry.file_name()))?;
+        }
+        fs::remove_dir(old_dir)?;
+        Ok(())
+    }
+}
+
+/// Drops a leading byte order mark and turns every line ending into `\n`,
+/// telling whether the text used CRLF
+fn normalize_draft(content: &str) -> (String, bool) {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let crlf = content.contains("\r\n");
+    (content.replace("\r\n", "\n").replace('\r', "\n"), crlf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn customized_fields_lists_only_changed_settings() {
+        assert!(Settings::default().customized_fields().is_empty());
+        let settings = Settings { tab_width: 8, vim_mode: true, ..Default::default() };
+        let fields: Vec<String> = settings.customized_fields().into_iter().collect();
+        assert_eq!(fields, ["tab_width", "vim_mode"]);
+    }
+
+    #[test]
+    fn number_settings_can_be_set_by_field_name() {
+        let mut settings = Settings::default();
+        settings.set_number("flow_warn_secs", 45).unwrap();
+        assert_eq!(settings.flow_warn_secs, 45);
+        assert!(settings.set_number("vim_mode", 1).is_err());
+        assert!(!settings.is_number("storage_path"));
+    }
+
+    #[test]
+    fn drafts_load_without_bom_or_crlf() {
+        let (content, crlf) = normalize_draft("\u{feff}# Title\r\n\r\nBody\r\n");
+        assert_eq!(content, "# Title\n\nBody\n");
+        assert!(crlf);
+        assert_eq!(LineEndings::Auto.separator(crlf), "\r\n");
+
+        let (content, crlf) = normalize_draft("old\rmac\n");
+        assert_eq!(content, "old\nmac\n");
+        assert!(!crlf);
+    }
+
+    #[test]
+    fn unknown_leader_actions_are_skipped() {
+        let json = r#"{"leader_mappings": {"w": "save", "x": "launch_rockets"}, "tab_width": 2}"#;
+        let settings: Settings = serde_json::from_str(json).unwrap();
+        assert_eq!(settings.leader_mappings.len(), 1);
+        assert_eq!(settings.leader_mappings["w"], LeaderAction::Save);
+        assert_eq!(settings.tab_width, 2);
+    }
 }
@@ -1,5 +1,7 @@
 use anyhow::Result;
 use crossterm::{
+    cursor::SetCursorStyle,
+    event::{DisableBracketedPaste, EnableBracketedPaste},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
@@ -11,12 +13,16 @@ pub type Tui = ratatui::Terminal<CrosstermBackend<std::io::Stdout>>;
 pub fn init() -> Result<Tui> {
     stdout().execute(EnterAlternateScreen)?;
     enable_raw_mode()?;
+    // Pastes arrive as one event instead of a flood of key presses
+    stdout().execute(EnableBracketedPaste)?;
     let backend = CrosstermBackend::new(stdout());
     let terminal = ratatui::Terminal::new(backend)?;
     Ok(terminal)
 }
 
 pub fn restore() -> Result<()> {
+    stdout().execute(DisableBracketedPaste)?;
+    stdout().execute(SetCursorStyle::DefaultUserShape)?;
     stdout().execute(LeaveAlternateScreen)?;
     disable_raw_mode()?;
     Ok(())
@@ -1,25 +1,48 @@
-use crate::app::{App, Mode, EditorMode, PopupAction};
-use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use crate::app::{expand_tabs, find_matches, App, MENU_ITEMS, SETTING_KEYS, Mode, EditorMode, PopupAction, SearchSource};
+use crate::diff::{self, DiffKind};
+use crate::highlight;
+use crate::stats;
+use crate::storage::{FlowTimerStyle, HistoryOrder, LineEndings, NumberFormat, SplashContent, Storage, ThemeName};
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag, TagEnd};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Clear},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Clear},
     Frame,
 };
 
 pub fn ui(f: &mut Frame, app: &mut App) {
     let area = f.area();
+    // Buffers get swapped in and out, so keep the tab width in step every frame
+    app.textarea.set_tab_length(app.settings.tab_width.clamp(1, u8::MAX as usize) as u8);
+    app.textarea.set_hard_tab_indent(!app.settings.soft_tabs);
+    // Paint the theme's background first; widgets without colors of their own keep it
+    f.render_widget(Block::default().style(theme(app.theme()).base), area);
 
     match app.mode {
         Mode::Splash => render_splash(f, app, area),
         Mode::Menu => render_menu(f, app, area),
-        Mode::Writing => render_writing(f, app, area),
+        Mode::Writing => {
+            render_writing(f, app, area);
+            if app.clear_pending {
+                render_clear_prompt(f, app, area);
+            }
+        }
         Mode::Flow => render_flow(f, app, area),
         Mode::FlowHistory => render_history(f, app, area),
         Mode::Settings => render_settings(f, app, area),
         Mode::Drafts => render_drafts(f, app, area),
         Mode::SpellCheck => render_spellcheck(f, app, area),
+        Mode::Versions => render_versions(f, app, area),
+        Mode::Diff => render_diff(f, app, area),
+        Mode::Search => render_search(f, app, area),
+        Mode::Outline => render_outline(f, app, area),
+        Mode::FlowRecovery => render_flow_recovery(f, app, area),
+        Mode::Snippets => render_snippets(f, app, area),
+        Mode::Calendar => render_calendar(f, app, area),
+        Mode::FlowSummary => render_flow_summary(f, app, area),
+        Mode::Locked => render_locked(f, app, area),
         Mode::PopupInput => {
              // Render whatever is behind? Usually writing or Drafts.
              // We need to know previous mode, but app only has current mode.
@@ -28,23 +51,96 @@ pub fn ui(f: &mut Frame, app: &mut App) {
              // Render Drafts if action suggests rename.
              // For now, just render popup centered.
              render_popup(f, app, area);
+             if let Some(target) = &app.rename_overwrite_pending {
+                 render_rename_prompt(f, target, area);
+             }
         }
     }
 
     // Overlay message
     if let Some(msg) = &app.message {
-        let msg_len = msg.len() as u16 + 4;
-        let msg_rect = Rect::new(area.width - msg_len, area.height - 1, msg_len, 1);
+        let msg_rect = message_rect(msg, area);
         let p = Paragraph::new(msg.as_str())
             .style(Style::default().bg(Color::Yellow).fg(Color::Black).add_modifier(Modifier::BOLD));
         f.render_widget(p, msg_rect);
     }
+
+    if let Some(loading) = &app.loading {
+        render_loading(f, app, &loading.label(), area);
+    }
+}
+
+/// The bottom-right corner the overlay message sits in. Long messages are
+/// cut off on narrow terminals rather than pushed off screen.
+fn message_rect(msg: &str, area: Rect) -> Rect {
+    let width = (msg.chars().count() as u16).saturating_add(4).min(area.width);
+    Rect::new(area.width.saturating_sub(width), area.height.saturating_sub(1), width, area.height.min(1))
+}
+
+fn render_loading(f: &mut Frame, app: &App, what: &str, area: Rect) {
+    const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+    let frame = (app.loading_since.elapsed().as_millis() / 250) as usize % SPINNER.len();
+    let text = format!(" {} Loading {}… (Esc to stop) ", SPINNER[frame], what);
+    let width = (text.chars().count() as u16 + 2).min(area.width);
+    let rect = Rect::new(area.x + (area.width - width) / 2, area.y + area.height / 2, width, 3.min(area.height));
+    f.render_widget(Clear, rect);
+    f.render_widget(Paragraph::new(text).block(Block::default().borders(Borders::ALL)), rect);
+}
+
+/// Base colors and list selection style for a theme
+struct Theme {
+    base: Style,
+    highlight: Style,
+    /// Editor line under the cursor, outside focus mode
+    cursor_line: Style,
+    /// Markdown and code in the preview lose their colors too
+    monochrome: bool,
+}
+
+fn theme(name: ThemeName) -> Theme {
+    let (base, highlight) = match name {
+        ThemeName::Terminal => (Style::default(), Style::default().bg(Color::DarkGray)),
+        ThemeName::Cream => (
+            Style::default().fg(Color::Rgb(40, 40, 40)).bg(Color::Rgb(250, 247, 240)),
+            Style::default().bg(Color::Rgb(220, 212, 196)),
+        ),
+        ThemeName::Dusk => (
+            Style::default().fg(Color::Rgb(205, 214, 244)).bg(Color::Rgb(30, 32, 48)),
+            Style::default().bg(Color::Rgb(69, 71, 90)),
+        ),
+        ThemeName::Forest => (
+            Style::default().fg(Color::Rgb(214, 222, 200)).bg(Color::Rgb(28, 38, 30)),
+            Style::default().bg(Color::Rgb(60, 82, 62)),
+        ),
+        // The terminal's own text and background, with nothing but the
+        // cursor line set apart
+        ThemeName::Paper => {
+            return Theme {
+                base: Style::default(),
+                highlight: Style::default().add_modifier(Modifier::REVERSED),
+                cursor_line: Style::default().add_modifier(Modifier::BOLD),
+                monochrome: true,
+            };
+        }
+    };
+    Theme { base, highlight: highlight.add_modifier(Modifier::BOLD), cursor_line: Style::default(), monochrome: false }
+}
+
+/// Drops foreground and background colors, keeping bold, italics and the like
+fn strip_colors(lines: Vec<Line<'static>>) -> Vec<Line<'static>> {
+    let plain = |style: Style| Style { fg: None, bg: None, underline_color: None, ..style };
+    lines.into_iter().map(|line| {
+        let spans = line.spans.into_iter()
+            .map(|span| Span::styled(span.content, plain(span.style)))
+            .collect();
+        Line { spans, style: plain(line.style), alignment: line.alignment }
+    }).collect()
 }
 
 fn render_splash(f: &mut Frame, app: &App, area: Rect) {
     use ratatui::layout::Alignment;
     
-    let output = vec![
+    let mut output = vec![
         Line::from(""),
         Line::from(""),
         Line::from(""),
@@ -57,44 +153,242 @@ fn render_splash(f: &mut Frame, app: &App, area: Rect) {
         ]),
         Line::from(""),
         Line::from(""),
-        Line::from(vec![
+    ];
+
+    if let Some(extra) = &app.splash_line {
+        output.push(Line::from(vec![
+            Span::styled(extra.clone(), Style::default().fg(Color::Green).add_modifier(Modifier::ITALIC)),
+        ]));
+    } else {
+        output.push(Line::from(vec![
             Span::raw("Created by "),
             Span::styled("Tim Apple", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+        ]));
+        output.push(Line::from(vec![
+            Span::styled("timapple.com", Style::default().fg(Color::Blue).add_modifier(Modifier::ITALIC)),
+        ]));
+    }
+
+    output.extend([
+        Line::from(""),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled(
+                if app.settings.splash_auto_advance { "Press any key to continue..." } else { "Press Enter to continue..." },
+                Style::default().fg(Color::DarkGray).add_modifier(Modifier::DIM),
+            )
         ]),
+    ]);
+
+    let p = Paragraph::new(output)
+        .wrap(ratatui::widgets::Wrap { trim: true })
+        .alignment(Alignment::Center);
+    f.render_widget(p, area);
+}
+
+fn render_menu(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(if Storage::is_read_only() { " Menu (READ-ONLY) " } else { " Menu " });
+    let inner = block.inner(chunks[0]);
+    f.render_widget(block, chunks[0]);
+
+    let warning_height = if app.storage_warning.is_some() { 3 } else { 0 };
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0), Constraint::Length(warning_height)])
+        .split(inner);
+
+    f.render_widget(Paragraph::new(Line::from(vec![Span::raw(" writeapp ").bold()])), rows[0]);
+
+    let items: Vec<ListItem> = MENU_ITEMS.iter().map(|(key, label)| {
+        if *key == 'f' {
+            ListItem::new(format!(" [{}] {} ({} min)", key, label, app.settings.last_flow_minutes))
+        } else {
+            ListItem::new(format!(" [{}] {}", key, label))
+        }
+    }).collect();
+    // The selection only stands out while the menu, not the recent list, has the keys
+    let highlight = if app.recent_focused {
+        Style::default()
+    } else {
+        theme(app.theme()).highlight
+    };
+    let list = List::new(items).highlight_style(highlight);
+    f.render_stateful_widget(list, rows[1], &mut app.menu_state);
+
+    if let Some(warning) = &app.storage_warning {
+        let p = Paragraph::new(vec![
+            Line::from(""),
+            Line::from(vec![
+                Span::styled(format!(" ⚠ {}", warning), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+            ]),
+        ]).wrap(ratatui::widgets::Wrap { trim: false });
+        f.render_widget(p, rows[2]);
+    }
+
+    render_recent(f, app, chunks[1]);
+
+    if app.quit_pending {
+        render_quit_prompt(f, app, area);
+    }
+}
+
+fn render_quit_prompt(f: &mut Frame, app: &App, area: Rect) {
+    use ratatui::layout::Alignment;
+
+    let popup_area = centered_rect(50, 20, area);
+    f.render_widget(Clear, popup_area);
+
+    let question = if app.has_unsaved_changes() {
+        Line::styled("Quit? You have unsaved changes.", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))
+    } else {
+        Line::from("Quit writeapp?")
+    };
+    let output = vec![
+        Line::from(""),
+        question,
+        Line::from(""),
         Line::from(vec![
-            Span::styled("timapple.com", Style::default().fg(Color::Blue).add_modifier(Modifier::ITALIC)),
+            Span::styled("[y/q] Quit", Style::default().fg(Color::Red)),
+            Span::raw("   "),
+            Span::styled("[any other key] Stay", Style::default().fg(Color::Green)),
+        ]),
+    ];
+
+    let p = Paragraph::new(output)
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title(" Quit "));
+    f.render_widget(p, popup_area);
+}
+
+fn render_clear_prompt(f: &mut Frame, app: &App, area: Rect) {
+    use ratatui::layout::Alignment;
+
+    let popup_area = centered_rect(50, 20, area);
+    f.render_widget(Clear, popup_area);
+
+    let question = if app.dirty {
+        Line::styled("Clear the buffer? You have unsaved changes.", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))
+    } else {
+        Line::from("Clear the buffer?")
+    };
+    let output = vec![
+        Line::from(""),
+        question,
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("[k] Keep draft name", Style::default().fg(Color::Red)),
+            Span::raw("   "),
+            Span::styled("[n] New untitled draft", Style::default().fg(Color::Red)),
         ]),
+        Line::from(Span::styled("[any other key] Cancel", Style::default().fg(Color::Green))),
+    ];
+
+    let p = Paragraph::new(output)
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title(" Clear Buffer "));
+    f.render_widget(p, popup_area);
+}
+
+fn render_rename_prompt(f: &mut Frame, target: &str, area: Rect) {
+    use ratatui::layout::Alignment;
+
+    let popup_area = centered_rect(50, 20, area);
+    f.render_widget(Clear, popup_area);
+
+    let output = vec![
         Line::from(""),
+        Line::styled(format!("Overwrite existing {}?", target), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
         Line::from(""),
         Line::from(vec![
-            Span::styled("Press any key to continue...", Style::default().fg(Color::DarkGray).add_modifier(Modifier::DIM))
+            Span::styled("[y] Overwrite", Style::default().fg(Color::Red)),
+            Span::raw("   "),
+            Span::styled("[s] Add a number", Style::default().fg(Color::Yellow)),
         ]),
+        Line::from(Span::styled("[any other key] Edit the name", Style::default().fg(Color::Green))),
     ];
 
     let p = Paragraph::new(output)
-        .alignment(Alignment::Center);
-    f.render_widget(p, area);
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title(" Rename "));
+    f.render_widget(p, popup_area);
 }
 
-fn render_menu(f: &mut Frame, _app: &App, area: Rect) {
+/// Nothing of the open text is drawn while locked
+fn render_locked(f: &mut Frame, app: &App, area: Rect) {
+    use ratatui::layout::Alignment;
+
+    let popup_area = centered_rect(50, 20, area);
+    let prompt = if app.settings.lock_passphrase.is_empty() {
+        "Press Enter to unlock"
+    } else {
+        "Enter passphrase to unlock"
+    };
     let output = vec![
-        Line::from(vec![Span::raw(" writeapp ").bold()]),
         Line::from(""),
-        Line::from(" [n] New Draft"),
-        Line::from(" [f] Flow Mode (10 min)"),
-        Line::from(" [5] Flow Mode (5 min)"),
-        Line::from(" [1] Flow Mode (15 min)"),
-        Line::from(" [h] History"),
-        Line::from(" [d] Drafts"),
-        Line::from(" [s] Settings"),
-        Line::from(" [q] Quit"),
+        Line::styled("Locked", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+        Line::from(""),
+        Line::from(prompt),
+        Line::styled("•".repeat(app.lock_input.chars().count()), Style::default().fg(Color::Yellow)),
     ];
 
-    let block = Block::default()
-        .borders(Borders::ALL)
-        .title(" Menu ");
-    let p = Paragraph::new(output).block(block);
-    f.render_widget(p, area);
+    let p = Paragraph::new(output)
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(p, popup_area);
+}
+
+fn render_recent(f: &mut Frame, app: &mut App, area: Rect) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(4)])
+        .split(area);
+
+    let title = if app.recent_focused {
+        " Recent Drafts (Enter to open, Tab to go back) "
+    } else {
+        " Recent Drafts (Tab to select) "
+    };
+    let block = Block::default().borders(Borders::ALL).title(title);
+
+    if app.recent_drafts.is_empty() {
+        let p = Paragraph::new(" No drafts yet").style(Style::default().fg(Color::DarkGray)).block(block);
+        f.render_widget(p, rows[0]);
+    } else {
+        let items: Vec<ListItem> = app.recent_drafts.iter().map(|d| ListItem::new(format!(" {}", d))).collect();
+        let highlight = if app.recent_focused {
+            theme(app.theme()).highlight
+        } else {
+            Style::default()
+        };
+        let list = List::new(items).block(block).highlight_style(highlight);
+        f.render_stateful_widget(list, rows[0], &mut app.recent_state);
+    }
+
+    let last_flow = match &app.last_flow {
+        Some(entry) => format!(
+            " {} · {} min · {} words",
+            entry.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+            entry.duration_minutes,
+            app.settings.number_format.format(stats::count_words(&entry.text))
+        ),
+        None => " No flow sessions yet".to_string(),
+    };
+    let streak = stats::streak(&app.daily_words, app.settings.streak_min_words, chrono::Local::now().date_naive());
+    let last_flow = vec![
+        Line::from(last_flow),
+        Line::from(format!(" Streak: {} day{}", streak, if streak == 1 { "" } else { "s" })),
+    ];
+    let p = Paragraph::new(last_flow)
+        .style(Style::default().fg(Color::DarkGray))
+        .block(Block::default().borders(Borders::ALL).title(" Last Flow Session "));
+    f.render_widget(p, rows[1]);
 }
 
 fn render_writing(f: &mut Frame, app: &mut App, area: Rect) {
@@ -117,13 +411,66 @@ fn render_writing(f: &mut Frame, app: &mut App, area: Rect) {
         height: chunks[0].height.saturating_sub(2), // Leave room at bottom
     };
 
-    if app.preview_mode_active {
-         let text_content = app.textarea.lines().join("\n");
-         let formatted_lines = parse_markdown_to_lines(&text_content); 
+    // Tab strip in the breathing-room line, only once a second buffer is open
+    if app.buffers.len() > 1 {
+        let strip = Rect { x: text_area.x, y: chunks[0].y, width: target_width, height: 1 };
+        render_buffer_tabs(f, app, strip);
+    }
+
+    if let Some(original) = app.history_original.as_ref().filter(|_| app.show_changes) {
+        let current = app.textarea.lines().join("\n");
+        let changes = diff::word_diff(original, &current);
+        let added: usize = changes.iter().flatten()
+            .filter(|(kind, _)| *kind == DiffKind::Added)
+            .map(|(_, text)| stats::count_words(text))
+            .sum();
+        let lines: Vec<Line> = changes.into_iter().map(|segments| {
+            Line::from(segments.into_iter().map(|(kind, text)| match kind {
+                DiffKind::Same => Span::raw(text),
+                DiffKind::Added => Span::styled(text, Style::default().fg(Color::Green)),
+                DiffKind::Removed => Span::styled(text, Style::default().fg(Color::Red).add_modifier(Modifier::CROSSED_OUT)),
+            }).collect::<Vec<_>>())
+        }).collect();
+        let title = format!(" Changes since the flow session (+{} words, Alt+D to edit) ", app.settings.number_format.format(added));
+        app.preview_scroll = app.preview_scroll.min(lines.len().saturating_sub(1));
+        let p = Paragraph::new(lines)
+            .wrap(ratatui::widgets::Wrap { trim: false })
+            .scroll((u16::try_from(app.preview_scroll).unwrap_or(u16::MAX), 0))
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(p, text_area);
+    } else if app.preview_mode_active && app.is_oversized() {
+        // Laying out a huge file every frame would stall, so only the
+        // visible lines are shown, as they are
+        let lines = app.textarea.lines();
+        app.preview_scroll = app.preview_scroll.min(lines.len().saturating_sub(1));
+        let end = (app.preview_scroll + text_area.height as usize).min(lines.len());
+        let visible: Vec<Line> = lines[app.preview_scroll..end]
+            .iter()
+            .map(|l| Line::from(expand_tabs(l, app.settings.tab_width)))
+            .collect();
+        let title = format!(" Read Only (over {} KB) ", app.settings.max_draft_kb);
+        let p = Paragraph::new(visible).block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(p, text_area);
+    } else if app.preview_mode_active {
+         // Paragraph renders a tab as nothing at all, so lay the tab stops out here
+         let text_content = app.textarea.lines().iter()
+             .map(|l| expand_tabs(l, app.settings.tab_width))
+             .collect::<Vec<_>>()
+             .join("\n");
+         // Plain text drafts only get their paragraphs reflowed, markdown is rendered
+         let (formatted_lines, title) = if app.is_markdown_buffer() {
+             (parse_markdown_to_lines(&text_content, text_area.width.saturating_sub(2)), " Preview (Markdown Read Only) ")
+         } else {
+             (reflow_plain_text(&text_content), " Preview (Plain Text Read Only) ")
+         };
+         let formatted_lines = if theme(app.theme()).monochrome { strip_colors(formatted_lines) } else { formatted_lines };
          
-         let block = Block::default().borders(Borders::ALL).title(" Preview (Markdown Read Only) ");
+         let block = Block::default().borders(Borders::ALL).title(title);
+         // Clamped to the unwrapped line count, so End can't scroll into nothing
+         app.preview_scroll = app.preview_scroll.min(formatted_lines.len().saturating_sub(1));
          let p = Paragraph::new(formatted_lines)
             .wrap(ratatui::widgets::Wrap { trim: false })
+            .scroll((u16::try_from(app.preview_scroll).unwrap_or(u16::MAX), 0))
             .block(block);
          f.render_widget(p, text_area);
          
@@ -137,42 +484,344 @@ fn render_writing(f: &mut Frame, app: &mut App, area: Rect) {
             app.textarea.set_cursor_line_style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD));
         } else {
             app.textarea.set_style(Style::default());
-            app.textarea.set_cursor_line_style(Style::default()); 
+            app.textarea.set_cursor_line_style(theme(app.theme()).cursor_line);
         }
         
-        f.render_widget(&app.textarea, text_area);
+        let folded = app.folded_ranges();
+        // tui-textarea puts line numbers, with a space either side, before the text
+        let mut gutter = 0;
+        if folded.is_empty() && !app.settings.visual_paragraph_spacing && app.find_query.is_none() {
+            f.render_widget(&app.textarea, text_area);
+            if app.textarea.line_number_style().is_some() {
+                gutter = app.textarea.lines().len().to_string().len() as u16 + 2;
+            }
+        } else {
+            render_editor_lines(f, app, text_area, &folded);
+        }
+        if app.settings.wrap_guide {
+            render_wrap_guide(f, text_area, gutter, app.wrap_column());
+        }
+        if app.settings.terminal_cursor {
+            show_terminal_cursor(f, text_area);
+        }
     }
 
-    let count = app.textarea.lines().join(" ").split_whitespace().count();
-    let mut status_parts = vec![format!("Words: {}", count)];
+    let count = app.word_count();
+
+    if !app.settings.status_template.is_empty() {
+        let status = expand_status_template(&app.settings.status_template, app, count);
+        f.render_widget(Paragraph::new(status).style(Style::default().fg(Color::DarkGray)), chunks[1]);
+        return;
+    }
+
+    let mut status_parts = vec![
+        app.current_draft_name.clone().unwrap_or_else(|| "untitled".to_string()),
+        format!("Words: {}", app.settings.number_format.format(count)),
+        format!("{}%", position_percent(app)),
+    ];
     
-    if app.settings.vim_mode {
-        let mode_str = match app.editor_mode {
-            EditorMode::Normal => "NORMAL",
-            EditorMode::Insert => "INSERT",
-            EditorMode::Visual => "VISUAL",
-        };
-        status_parts.push(glue_mode_status(mode_str));
+    if let Some(n) = app.misspelled_count.filter(|&n| n > 0) {
+        status_parts.push(format!("⚠ {}", n));
+    }
+
+    if Storage::is_read_only() {
+        status_parts.push("READ-ONLY".to_string());
+    }
+
+    if app.settings.show_wpm {
+        status_parts.push(format!("{} wpm", app.settings.number_format.format(app.wpm)));
+    }
+
+    if let Some(query) = &app.find_query {
+        let total: usize = app.textarea.lines().iter().map(|l| find_matches(l, query).len()).sum();
+        status_parts.push(format!(
+            "Find \"{}\": {} match{} (F3/Shift+F3, Esc to clear)",
+            query,
+            app.settings.number_format.format(total),
+            if total == 1 { "" } else { "es" }
+        ));
+    }
+
+    if app.vim_enabled() {
+        status_parts.push(glue_mode_status(editor_mode_label(app.editor_mode)));
+        if app.leader_pending {
+            status_parts.push("<leader>".to_string());
+        }
+        let pending = app.pending_keys();
+        if !pending.is_empty() {
+            status_parts.push(pending);
+        }
     }
     
+    if app.preview_mode_active || app.show_changes {
+        status_parts.push("↑/↓ j/k: Scroll | PgUp/PgDn | g/G: Top/Bottom | Esc: Back to editing".to_string());
+        let status = status_parts.join(" | ");
+        f.render_widget(Paragraph::new(status).style(Style::default().fg(Color::DarkGray)), chunks[1]);
+        return;
+    }
+
     status_parts.push("Esc: Menu | Ctrl+S: Save".to_string());
     
-    if app.settings.vim_mode && app.editor_mode == EditorMode::Visual {
+    if let Some((lines, chars)) = app.selection_counts() {
+        status_parts.push(format!(
+            "Sel: {} line{}, {} char{}",
+            app.settings.number_format.format(lines),
+            if lines == 1 { "" } else { "s" },
+            app.settings.number_format.format(chars),
+            if chars == 1 { "" } else { "s" }
+        ));
+    }
+
+    if app.vim_enabled() && app.editor_mode == EditorMode::Visual {
          status_parts.push("n: New Draft | y: Yank".to_string());
-    } else if app.settings.vim_mode && app.editor_mode == EditorMode::Normal {
+    } else if app.vim_enabled() && app.editor_mode == EditorMode::Normal {
          status_parts.push("Ctrl+R: Rename".to_string());
     } else {
-         status_parts.push("Ctrl+R: Rename | Ctrl+F: Focus | Ctrl+P: Preview | Ctrl+L: Spell Check".to_string());
+         let preview = if app.is_markdown_buffer() { "Preview" } else { "Reflow" };
+         status_parts.push(format!("Ctrl+R: Rename | Ctrl+F: Focus | Ctrl+P: {} | Ctrl+L: Spell Check", preview));
     }
 
     let status = status_parts.join(" | ");
     f.render_widget(Paragraph::new(status).style(Style::default().fg(Color::DarkGray)), chunks[1]);
 }
 
+/// Draws a dim bar down the hard-wrap column, in blank cells only so text
+/// running past it stays readable
+fn render_wrap_guide(f: &mut Frame, area: Rect, gutter: u16, column: usize) {
+    let x = area.x.saturating_add(gutter).saturating_add(u16::try_from(column).unwrap_or(u16::MAX));
+    if column == 0 || x >= area.right() {
+        return;
+    }
+    let buf = f.buffer_mut();
+    for y in area.top()..area.bottom() {
+        let cell = &mut buf[(x, y)];
+        if cell.symbol() == " " && !cell.modifier.contains(Modifier::REVERSED) {
+            cell.set_symbol("│").set_fg(Color::DarkGray);
+        }
+    }
+}
+
+/// Swaps the drawn cursor cell (the only reversed cell in the editor) for the
+/// terminal's cursor, so its shape can be set. tui-textarea keeps its scroll
+/// position private, so finding the drawn cell is the reliable way to place it.
+fn show_terminal_cursor(f: &mut Frame, area: Rect) {
+    let buf = f.buffer_mut();
+    for y in area.top()..area.bottom() {
+        for x in area.left()..area.right() {
+            let cell = &mut buf[(x, y)];
+            if cell.modifier.contains(Modifier::REVERSED) {
+                cell.modifier.remove(Modifier::REVERSED);
+                f.set_cursor_position((x, y));
+                return;
+            }
+        }
+    }
+}
+
+/// Joins hard-wrapped lines into paragraphs so the preview can rewrap them
+/// to the window; blank lines stay paragraph breaks.
+fn reflow_plain_text(text: &str) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !paragraph.is_empty() {
+                lines.push(Line::from(paragraph.join(" ")));
+                paragraph.clear();
+            }
+            lines.push(Line::from(""));
+        } else {
+            paragraph.push(line.trim());
+        }
+    }
+    if !paragraph.is_empty() {
+        lines.push(Line::from(paragraph.join(" ")));
+    }
+    lines
+}
+
+/// Renders the buffer with folded sections collapsed to a placeholder and,
+/// with visual paragraph spacing on, an extra blank row after each paragraph
+/// break. tui-textarea can't hide or add rows, so this draws the text, the
+/// selection and the cursor itself, scrolling the way its widget does; the
+/// buffer is left untouched.
+fn render_editor_lines(f: &mut Frame, app: &mut App, area: Rect, folded: &[(usize, usize)]) {
+    let (cursor_row, cursor_col) = app.textarea.cursor();
+    let base_style = if app.focus_mode_active { Style::default().fg(Color::DarkGray) } else { Style::default() };
+    let cursor_line_style = if app.focus_mode_active {
+        Style::default().fg(Color::White).add_modifier(Modifier::BOLD)
+    } else {
+        theme(app.theme()).cursor_line
+    };
+    let selection_style = app.textarea.selection_style();
+    let selection = app.textarea.selection_range();
+
+    let mut display: Vec<Line> = Vec::new();
+    let mut cursor_display_row = 0;
+    let mut row = 0;
+    let lines = app.textarea.lines();
+    let query = app.find_query.as_deref().filter(|q| !q.is_empty());
+    while row < lines.len() {
+        let matches = query.map(|q| find_matches(&lines[row], q)).unwrap_or_default();
+        let selected = selection.and_then(|range| selected_columns(range, row, lines[row].chars().count()));
+        if row == cursor_row {
+            cursor_display_row = display.len();
+            display.push(styled_editor_line(&lines[row], cursor_line_style, &matches, selected, selection_style, Some(cursor_col)));
+        } else if matches.is_empty() && selected.is_none() {
+            display.push(Line::styled(lines[row].clone(), base_style));
+        } else {
+            display.push(styled_editor_line(&lines[row], base_style, &matches, selected, selection_style, None));
+        }
+
+        let paragraph_break = row > 0 && lines[row].trim().is_empty() && !lines[row - 1].trim().is_empty();
+        if app.settings.visual_paragraph_spacing && paragraph_break {
+            display.push(Line::from(""));
+        }
+
+        if let Some(&(_, end)) = folded.iter().find(|(heading, _)| *heading == row) {
+            let hidden = end - row - 1;
+            display.push(Line::styled(
+                format!("  … ({} line{} folded)", hidden, if hidden == 1 { "" } else { "s" }),
+                Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+            ));
+            row = end;
+        } else {
+            row += 1;
+        }
+    }
+
+    // Only scroll as far as needed to keep the cursor in view
+    let (top, left) = app.editor_scroll;
+    let top = next_scroll_start(top, cursor_display_row, area.height as usize);
+    let left = next_scroll_start(left, cursor_col, area.width as usize);
+    app.editor_scroll = (top, left);
+
+    let scroll = (u16::try_from(top).unwrap_or(u16::MAX), u16::try_from(left).unwrap_or(u16::MAX));
+    f.render_widget(Paragraph::new(display).scroll(scroll), area);
+}
+
+/// First row (or column) to show so `cursor` stays within a `len`-long
+/// viewport that last started at `prev`, as tui-textarea scrolls
+fn next_scroll_start(prev: usize, cursor: usize, len: usize) -> usize {
+    if cursor < prev {
+        cursor
+    } else if prev + len <= cursor {
+        cursor + 1 - len.max(1)
+    } else {
+        prev
+    }
+}
+
+/// Columns of `row` covered by a selection running from its first position
+/// up to (not including) its second. A selection ending at a line's start
+/// still marks the line break of the rows before it.
+fn selected_columns(((start_row, start_col), (end_row, end_col)): ((usize, usize), (usize, usize)), row: usize, len: usize) -> Option<(usize, usize)> {
+    if row < start_row || row > end_row {
+        return None;
+    }
+    let from = if row == start_row { start_col } else { 0 };
+    // One past the end of the line stands for its newline
+    let to = if row == end_row { end_col } else { len + 1 };
+    (from < to).then_some((from, to))
+}
+
+/// Builds one editor row with find matches on a highlighted background (the
+/// one under the cursor in its own color), the selection in the textarea's
+/// selection style and, on the cursor row, the cursor drawn the way
+/// tui-textarea does: a reversed cell.
+fn styled_editor_line(
+    text: &str,
+    base: Style,
+    matches: &[(usize, usize)],
+    selected: Option<(usize, usize)>,
+    selection_style: Style,
+    cursor: Option<usize>,
+) -> Line<'static> {
+    let style_at = |col: usize| {
+        let mut style = base;
+        if let Some(&(start, _)) = matches.iter().find(|(start, end)| (*start..*end).contains(&col)) {
+            let bg = if cursor == Some(start) { Color::LightYellow } else { Color::Yellow };
+            style = style.bg(bg).fg(Color::Black);
+        }
+        if selected.is_some_and(|(from, to)| (from..to).contains(&col)) {
+            style = style.patch(selection_style);
+        }
+        if cursor == Some(col) {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        style
+    };
+
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut run = String::new();
+    let mut run_style = base;
+    let mut len = 0;
+    for (col, c) in text.chars().enumerate() {
+        let style = style_at(col);
+        if style != run_style && !run.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut run), run_style));
+        }
+        run_style = style;
+        run.push(c);
+        len = col + 1;
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(run, run_style));
+    }
+    // The cell past the last character shows the cursor or a selected line break
+    if cursor.is_some_and(|col| col >= len) || selected.is_some_and(|(_, to)| to > len) {
+        spans.push(Span::styled(" ", style_at(len)));
+    }
+    Line::from(spans)
+}
+
 fn glue_mode_status(mode: &str) -> String {
     format!("[{}]", mode)
 }
 
+fn editor_mode_label(mode: EditorMode) -> &'static str {
+    match mode {
+        EditorMode::Normal => "NORMAL",
+        EditorMode::Insert => "INSERT",
+        EditorMode::Visual => "VISUAL",
+    }
+}
+
+/// Cursor line as a percentage through the buffer
+fn position_percent(app: &App) -> usize {
+    let last = app.textarea.lines().len().saturating_sub(1);
+    if last == 0 {
+        return 0;
+    }
+    app.textarea.cursor().0 * 100 / last
+}
+
+/// Substitutes the known `{token}`s of a user status-bar template.
+/// Unknown tokens are left as-is so typos are visible.
+fn expand_status_template(template: &str, app: &App, words: usize) -> String {
+    let chars: usize = app.textarea.lines().iter().map(|l| l.chars().count()).sum();
+    // Average silent reading speed of ~200 words per minute
+    let reading_mins = words.div_ceil(200);
+    let mode = if app.vim_enabled() { editor_mode_label(app.editor_mode) } else { "EDIT" };
+    let draft = app.current_draft_name.as_deref().unwrap_or("untitled");
+    let dirty = if app.dirty { "[+]" } else { "" };
+    let session = app.session_start.elapsed().as_secs();
+
+    template
+        .replace("{words}", &app.settings.number_format.format(words))
+        .replace("{chars}", &app.settings.number_format.format(chars))
+        .replace("{reading}", &format!("{} min read", app.settings.number_format.format(reading_mins)))
+        .replace("{mode}", mode)
+        .replace("{draft}", draft)
+        .replace("{dirty}", dirty)
+        .replace("{percent}", &format!("{}%", position_percent(app)))
+        .replace("{selection}", &app.selection_counts().map(|(l, c)| format!("{}L {}C", l, c)).unwrap_or_default())
+        .replace("{wpm}", &app.settings.number_format.format(app.wpm))
+        .replace("{misspelled}", &app.misspelled_count.map(|n| n.to_string()).unwrap_or_default())
+        .replace("{time}", &chrono::Local::now().format("%H:%M").to_string())
+        .replace("{session}", &format!("{:02}:{:02}", session / 3600, (session % 3600) / 60))
+}
+
 fn render_flow(f: &mut Frame, app: &mut App, area: Rect) {
     // Calculate a centered text area with a max width (e.g. 100 chars)
     // This adds large side margins on wide screens for a better reading experience
@@ -192,7 +841,12 @@ fn render_flow(f: &mut Frame, app: &mut App, area: Rect) {
     // 1. Base text is dimmed (DarkGray)
     // 2. Active line is bright (White + Bold)
     // This creates a "fade" effect where only the current thought is in sharp focus.
-    app.textarea.set_style(Style::default().fg(Color::DarkGray));
+    // A pause in typing dims the rest a step further, leaving the active line as is
+    // (a fixed mid-gray: DIM on top of DarkGray is all but invisible on many terminals)
+    let rest = if app.flow_idle() { Color::Indexed(240) } else { Color::DarkGray };
+    // The timer and goal keep their colours and just fade
+    let dim = if app.flow_idle() { Modifier::DIM } else { Modifier::empty() };
+    app.textarea.set_style(Style::default().fg(rest));
     app.textarea.set_cursor_line_style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD));
     
     // Minimalist: No block borders
@@ -202,11 +856,19 @@ fn render_flow(f: &mut Frame, app: &mut App, area: Rect) {
     f.render_widget(&app.textarea, text_area);
 
     // Timer Overlay (Keep at absolute Bottom Right of screen)
-    let time_str = format!(
-        "{:02}:{:02}",
-        app.flow_remaining.as_secs() / 60,
-        app.flow_remaining.as_secs() % 60
-    );
+    let time_str = if app.flow_overtime.is_zero() {
+        format!(
+            "{:02}:{:02}",
+            app.flow_remaining.as_secs() / 60,
+            app.flow_remaining.as_secs() % 60
+        )
+    } else {
+        format!(
+            "+{:02}:{:02}",
+            app.flow_overtime.as_secs() / 60,
+            app.flow_overtime.as_secs() % 60
+        )
+    };
     
     let timer_width = 10;
     let timer_rect = Rect::new(
@@ -216,86 +878,660 @@ fn render_flow(f: &mut Frame, app: &mut App, area: Rect) {
         1
     );
     
-    let timer = Paragraph::new(time_str)
-        .style(Style::default().fg(if app.flow_remaining.as_secs() < 60 { Color::Red } else { Color::Green }));
-    f.render_widget(timer, timer_rect);
+    if app.settings.flow_timer_style != FlowTimerStyle::Bar {
+        let timer = Paragraph::new(time_str)
+            .style(Style::default().fg(flow_timer_color(app)).add_modifier(dim));
+        f.render_widget(timer, timer_rect);
+    }
+
+    if app.settings.flow_timer_style != FlowTimerStyle::Digits {
+        render_flow_bar(f, app, Rect::new(area.x, area.bottom().saturating_sub(1), area.width, 1));
+    }
+
+    if app.settings.show_wpm {
+        let wpm = format!("{} wpm", app.settings.number_format.format(app.wpm));
+        let wpm_width = wpm.chars().count() as u16;
+        let wpm_rect = Rect::new(
+            timer_rect.x.saturating_sub(wpm_width + 2),
+            timer_rect.y,
+            wpm_width,
+            1
+        );
+        f.render_widget(Paragraph::new(wpm).style(Style::default().fg(Color::DarkGray)), wpm_rect);
+    }
+
+    if app.flow_sentence_goal > 0 {
+        let goal = format!(
+            "{}/{} sentences",
+            app.settings.number_format.format(app.flow_sentences),
+            app.settings.number_format.format(app.flow_sentence_goal)
+        );
+        let color = if app.sentence_goal_reached { Color::Green } else { Color::DarkGray };
+        let goal_rect = Rect::new(area.x + 2, timer_rect.y, (goal.chars().count() as u16).min(area.width.saturating_sub(2)), 1);
+        f.render_widget(Paragraph::new(goal).style(Style::default().fg(color).add_modifier(dim)), goal_rect);
+    }
+
+    if app.flow_exit_pending {
+        render_flow_exit_prompt(f, area);
+    }
+}
+
+/// One row of line-drawing characters, as wide as the time left; it stays
+/// full in the overtime color once the timer runs out
+fn render_flow_bar(f: &mut Frame, app: &App, area: Rect) {
+    let duration = app.flow_duration.as_secs_f64();
+    let filled = if !app.flow_overtime.is_zero() || duration == 0.0 {
+        area.width
+    } else {
+        (area.width as f64 * app.flow_remaining.as_secs_f64() / duration).round() as u16
+    };
+    let bar = Line::from(vec![
+        Span::styled("━".repeat(filled as usize), Style::default().fg(flow_timer_color(app))),
+        Span::styled("─".repeat(area.width.saturating_sub(filled) as usize), Style::default().fg(Color::DarkGray)),
+    ]);
+    f.render_widget(Paragraph::new(bar), area);
+}
+
+fn flow_timer_color(app: &App) -> Color {
+    let remaining = app.flow_remaining.as_secs();
+    let settings = &app.settings;
+    if !app.flow_overtime.is_zero() {
+        parse_color(&settings.flow_overtime_color, Color::Magenta)
+    } else if remaining < settings.flow_warn_secs {
+        parse_color(&settings.flow_warn_color, Color::Red)
+    } else if remaining < settings.flow_caution_secs {
+        parse_color(&settings.flow_caution_color, Color::Yellow)
+    } else {
+        parse_color(&settings.flow_timer_color, Color::Green)
+    }
+}
+
+/// Parses a user color setting, falling back when it's empty or invalid
+fn parse_color(value: &str, fallback: Color) -> Color {
+    value.parse().unwrap_or(fallback)
+}
+
+fn render_flow_exit_prompt(f: &mut Frame, area: Rect) {
+    use ratatui::layout::Alignment;
+
+    let popup_area = centered_rect(50, 20, area);
+    f.render_widget(Clear, popup_area);
+
+    let output = vec![
+        Line::from(""),
+        Line::from("End session early?"),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("[s/Esc] End", Style::default().fg(Color::Green)),
+            Span::raw("   "),
+            Span::styled("[d] Discard", Style::default().fg(Color::Red)),
+            Span::raw("   "),
+            Span::styled("[n] Keep writing", Style::default().fg(Color::Yellow)),
+        ]),
+    ];
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" End Flow ");
+    let p = Paragraph::new(output)
+        .alignment(Alignment::Center)
+        .block(block);
+    f.render_widget(p, popup_area);
 }
 
 fn render_history(f: &mut Frame, app: &mut App, area: Rect) {
     let items: Vec<ListItem> = app.history.iter().map(|entry| {
-        let preview = entry.text.lines().next().unwrap_or("Empty").chars().take(50).collect::<String>();
         let content = format!(
-            "{} | {}m | {}", 
+            "{} | {}m | {} words | {}",
             entry.timestamp.format("%Y-%m-%d %H:%M"),
             entry.duration_minutes,
-            preview
+            app.settings.number_format.format(entry.words),
+            entry.preview
         );
         ListItem::new(content)
     }).collect();
 
+    let order = match app.history_order {
+        HistoryOrder::Date => "newest first",
+        HistoryOrder::Words => "most words first",
+        HistoryOrder::Duration => "longest first",
+    };
+    let empty = if app.history_hide_empty { ", empty hidden" } else { "" };
+    let source = match app.history_archive {
+        Some(year) => format!("Flow History - {} archive, {}{} (a for next archive, s to sort, e for empty)", year, order, empty),
+        None => format!("Flow History, {}{} (a for archives, s to sort, e for empty)", order, empty),
+    };
+    let shown = app.history_offset + app.history.len();
+    let title = if app.history_total > shown || app.history_offset > 0 {
+        format!(" {} - {}-{} of {} (PgUp/PgDn) ", source, app.history_offset + 1, shown, app.history_total)
+    } else {
+        format!(" {} ", source)
+    };
+    let block = Block::default().borders(Borders::ALL).title(title);
+    if app.history.is_empty() {
+        let hint = if app.history_hide_empty {
+            " No sessions with words in them (e to show empty ones)"
+        } else if app.history_archive.is_some() {
+            " No sessions in this archive"
+        } else {
+            " No flow sessions yet — press f from the menu to start one"
+        };
+        f.render_widget(Paragraph::new(hint).style(Style::default().fg(Color::DarkGray)).block(block), area);
+        return;
+    }
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title(" Flow History "))
-        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
+        .block(block)
+        .highlight_style(theme(app.theme()).highlight);
 
     f.render_stateful_widget(list, area, &mut app.history_state);
 }
 
 fn render_drafts(f: &mut Frame, app: &mut App, area: Rect) {
     let items: Vec<ListItem> = app.drafts.iter().map(|d| {
-        ListItem::new(Line::from(d.clone()))
+        let mut spans = vec![Span::raw(d.clone())];
+        if let Some(&words) = app.draft_words.get(d) {
+            spans.push(Span::styled(
+                format!("  {} word{}", app.settings.number_format.format(words), if words == 1 { "" } else { "s" }),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        ListItem::new(Line::from(spans))
     }).collect();
 
+    let block = Block::default().borders(Borders::ALL).title(" Drafts (Enter to open, Del to delete, v for versions) ");
+    if app.drafts.is_empty() {
+        let p = Paragraph::new(" No drafts yet — press n from the menu to start writing")
+            .style(Style::default().fg(Color::DarkGray))
+            .block(block);
+        f.render_widget(p, area);
+        return;
+    }
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title(" Drafts (Enter to open, Del to delete) "))
-        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
+        .block(block)
+        .highlight_style(theme(app.theme()).highlight);
 
     f.render_stateful_widget(list, area, &mut app.drafts_state);
 }
 
-fn render_settings(f: &mut Frame, app: &mut App, area: Rect) {
-    // Basic settings display
-    let _extension_label = if app.settings.default_extension == "txt" { "(txt)" } else { "(md)" };
-    let vim_status = if app.settings.vim_mode { "Enabled" } else { "Disabled" };
-    let splash_status = if app.settings.show_splash_screen { "Enabled" } else { "Disabled" };
-    let spellcheck_status = if app.settings.spellcheck_enabled { "Enabled" } else { "Disabled" };
-    
-    let output = vec![
-        Line::from(vec![Span::raw(" Settings ").bold()]),
+fn render_versions(f: &mut Frame, app: &mut App, area: Rect) {
+    let items: Vec<ListItem> = app.versions.iter().map(|v| {
+        // Snapshot names are UTC timestamps; show them in local time when they parse
+        let label = chrono::NaiveDateTime::parse_from_str(v, "%Y%m%d-%H%M%S%.3f")
+            .map(|t| t.and_utc().with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|_| v.clone());
+        ListItem::new(Line::from(label))
+    }).collect();
+
+    let title = format!(" Versions of {} (Enter to restore, d to diff, Esc to go back) ", app.versions_draft);
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(theme(app.theme()).highlight);
+
+    f.render_stateful_widget(list, area, &mut app.versions_state);
+}
+
+fn render_buffer_tabs(f: &mut Frame, app: &App, area: Rect) {
+    let mut spans = Vec::new();
+    for (i, (name, dirty)) in app.buffer_labels().into_iter().enumerate() {
+        let label = format!(" {}{} ", name, if dirty { "*" } else { "" });
+        let style = if i == app.active_buffer {
+            Style::default().fg(Color::Black).bg(Color::Gray)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        spans.push(Span::styled(label, style));
+        spans.push(Span::raw(" "));
+    }
+    f.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+fn render_flow_summary(f: &mut Frame, app: &App, area: Rect) {
+    use ratatui::layout::Alignment;
+
+    let secs = app.flow_elapsed.as_secs();
+    let words = app.word_count();
+    let wpm = if secs == 0 { 0 } else { words * 60 / secs as usize };
+
+    let mut output = vec![
+        Line::from(""),
+        Line::from(Span::styled("Session complete", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))),
+        Line::from(""),
+        Line::from(format!("Duration: {:02}:{:02}", secs / 60, secs % 60)),
+        Line::from(format!("Words: {}", app.settings.number_format.format(words))),
+        Line::from(format!("Pace: {} wpm", app.settings.number_format.format(wpm))),
         Line::from(""),
         Line::from(vec![
-            Span::raw(" [e] Default Extension: "),
-            Span::raw(app.settings.default_extension.clone()).bold().fg(Color::Yellow),
-        ]),
-        Line::from(vec![
-            Span::raw(" [v] Vim Mode: "),
-            Span::raw(vim_status).bold().fg(if app.settings.vim_mode { Color::Green } else { Color::Red }),
-        ]),
-        Line::from(vec![
-            Span::raw(" [s] Splash Screen: "),
-            Span::raw(splash_status).bold().fg(if app.settings.show_splash_screen { Color::Green } else { Color::Red }),
-        ]),
-        Line::from("(Splash always shows on version upgrades)"),
-        Line::from(vec![
-            Span::raw(" [c] Spell Check: "),
-            Span::raw(spellcheck_status).bold().fg(if app.settings.spellcheck_enabled { Color::Green } else { Color::Red }),
+            Span::styled("[s] Save", Style::default().fg(Color::Green)),
+            Span::raw("   "),
+            Span::styled("[w] Save and keep writing", Style::default().fg(Color::Cyan)),
+            Span::raw("   "),
+            Span::styled("[d] Discard", Style::default().fg(Color::Red)),
         ]),
+    ];
+    if let Some(problem) = &app.history_unreadable {
+        output.push(Line::from(""));
+        output.push(Line::styled(format!("Not saved: {}", problem), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
+        output.push(Line::from(Span::styled(
+            "[o] Move it aside (restoring the last good copy) and save",
+            Style::default().fg(Color::Yellow),
+        )));
+    }
+
+    let p = Paragraph::new(output)
+        .alignment(Alignment::Center)
+        .wrap(ratatui::widgets::Wrap { trim: true })
+        .block(Block::default().borders(Borders::ALL).title(" Flow Summary "));
+    f.render_widget(p, centered_rect(60, 40, area));
+}
+
+fn render_flow_recovery(f: &mut Frame, app: &App, area: Rect) {
+    use ratatui::layout::Alignment;
+
+    let mut output = vec![
+        Line::from(""),
+        Line::from(Span::styled("An interrupted flow session was found", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))),
+        Line::from(""),
+    ];
+    if let Some(recovery) = &app.pending_recovery {
+        let remaining = recovery.duration_secs.saturating_sub(recovery.elapsed_secs);
+        output.push(Line::from(format!(
+            "Last saved {} · {} words · {:02}:{:02} left",
+            recovery.saved_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+            app.settings.number_format.format(stats::count_words(&recovery.text)),
+            remaining / 60,
+            remaining % 60
+        )));
+    }
+    output.extend([
+        Line::from(""),
         Line::from(vec![
-            Span::raw(" Storage Path: "),
-            Span::raw(app.settings.storage_path.clone()).italic().fg(Color::Cyan),
+            Span::styled("[r] Resume", Style::default().fg(Color::Green)),
+            Span::raw("   "),
+            Span::styled("[s] Save text as draft", Style::default().fg(Color::Cyan)),
+            Span::raw("   "),
+            Span::styled("[x] Discard", Style::default().fg(Color::Red)),
         ]),
-        Line::from("(Edit storage path in settings.json)"),
+    ]);
+
+    let p = Paragraph::new(output)
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title(" Recover Flow Session "));
+    f.render_widget(p, centered_rect(70, 40, area));
+}
+
+fn render_outline(f: &mut Frame, app: &mut App, area: Rect) {
+    let items: Vec<ListItem> = app.outline.iter().map(|h| {
+        let indent = "  ".repeat(h.level.saturating_sub(1));
+        let style = if h.level == 1 {
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        ListItem::new(Line::from(vec![
+            Span::raw(format!(" {}", indent)),
+            Span::styled(h.text.clone(), style),
+            Span::styled(format!("  :{}", h.line + 1), Style::default().fg(Color::DarkGray)),
+        ]))
+    }).collect();
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(" Outline (Enter to jump, Esc to go back) "))
+        .highlight_style(theme(app.theme()).highlight);
+    f.render_stateful_widget(list, area, &mut app.outline_state);
+}
+
+fn render_calendar(f: &mut Frame, app: &App, area: Rect) {
+    use chrono::{Datelike, Days};
+
+    let month = app.calendar_month;
+    let today = chrono::Local::now().date_naive();
+    let min_words = app.settings.streak_min_words;
+
+    let mut output = vec![
+        Line::from(Span::styled(format!(" {}", month.format("%B %Y")), Style::default().add_modifier(Modifier::BOLD))),
+        Line::from(""),
+        Line::from(Span::styled(" Mo  Tu  We  Th  Fr  Sa  Su", Style::default().fg(Color::DarkGray))),
+    ];
+
+    // Pad the first week up to the month's starting weekday
+    let mut week = vec![Span::raw("    "); month.weekday().num_days_from_monday() as usize];
+    let mut day = month;
+    while day.month() == month.month() {
+        let words = app.daily_words.get(&day).copied().unwrap_or(0);
+        let mut style = if words > 0 && words >= min_words {
+            Style::default().fg(Color::Black).bg(Color::Green)
+        } else if words > 0 {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        if day == today {
+            style = style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+        }
+        week.push(Span::raw(" "));
+        week.push(Span::styled(format!("{:>2}", day.day()), style));
+        week.push(Span::raw(" "));
+        if day.weekday() == chrono::Weekday::Sun {
+            output.push(Line::from(std::mem::take(&mut week)));
+        }
+        match day.checked_add_days(Days::new(1)) {
+            Some(d) => day = d,
+            None => break,
+        }
+    }
+    if !week.is_empty() {
+        output.push(Line::from(week));
+    }
+
+    let streak = stats::streak(&app.daily_words, min_words, today);
+    let month_words: usize = app.daily_words.range(month..).take_while(|(d, _)| d.month() == month.month()).map(|(_, w)| w).sum();
+    output.extend([
         Line::from(""),
-        Line::from(" [Esc] Back to Menu"),
+        Line::from(format!(" Streak: {} day{}", streak, if streak == 1 { "" } else { "s" })),
+        Line::from(format!(" Words this month: {}", app.settings.number_format.format(month_words))),
+        Line::from(Span::styled(format!(" Days count at {}+ words (change with [g] in Settings)", min_words), Style::default().fg(Color::DarkGray))),
+    ]);
+
+    let p = Paragraph::new(output)
+        .block(Block::default().borders(Borders::ALL).title(" Writing Calendar (←/→ month, Esc to go back) "));
+    f.render_widget(p, area);
+}
+
+fn render_snippets(f: &mut Frame, app: &mut App, area: Rect) {
+    let items: Vec<ListItem> = app.snippets.iter().map(|(trigger, body)| {
+        let preview = body.lines().next().unwrap_or("");
+        ListItem::new(Line::from(vec![
+            Span::styled(format!(" {:<12}", trigger), Style::default().fg(Color::Cyan)),
+            Span::styled(preview.to_string(), Style::default().fg(Color::DarkGray)),
+        ]))
+    }).collect();
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(" Snippets (Enter to insert, Esc to go back) "))
+        .highlight_style(theme(app.theme()).highlight);
+    f.render_stateful_widget(list, area, &mut app.snippets_state);
+}
+
+fn render_search(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .split(area);
+
+    let input = Paragraph::new(format!("{}█", app.search_query))
+        .block(Block::default().borders(Borders::ALL).title(" Search drafts and flow history (Enter to search/open, Esc to go back) "));
+    f.render_widget(input, chunks[0]);
+
+    let items: Vec<ListItem> = app.search_results.iter().map(|result| {
+        let (tag, name) = match &result.source {
+            SearchSource::Draft(name) => (Span::styled("[draft] ", Style::default().fg(Color::Cyan)), name.clone()),
+            SearchSource::Flow(entry) => (
+                Span::styled("[flow]  ", Style::default().fg(Color::Magenta)),
+                entry.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string(),
+            ),
+        };
+        ListItem::new(Line::from(vec![
+            tag,
+            Span::raw(name).bold(),
+            Span::raw("  "),
+            Span::styled(result.snippet.clone(), Style::default().fg(Color::DarkGray)),
+        ]))
+    }).collect();
+
+    let title = format!(" Results ({}) ", app.search_results.len());
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(theme(app.theme()).highlight);
+    f.render_stateful_widget(list, chunks[1], &mut app.search_state);
+}
+
+fn render_diff(f: &mut Frame, app: &App, area: Rect) {
+    let lines: Vec<Line> = app.diff_lines.iter().map(|(kind, text)| {
+        match kind {
+            DiffKind::Same => Line::from(format!("  {}", text)),
+            DiffKind::Added => Line::styled(format!("+ {}", text), Style::default().fg(Color::Green)),
+            DiffKind::Removed => Line::styled(format!("- {}", text), Style::default().fg(Color::Red)),
+        }
+    }).collect();
+
+    let title = format!(" Diff: {} (j/k to scroll, Esc to go back) ", app.diff_title);
+    let p = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .scroll((app.diff_scroll, 0));
+    f.render_widget(p, area);
+}
+
+fn render_settings(f: &mut Frame, app: &mut App, area: Rect) {
+    // One row per SETTING_KEYS entry, in its order, so the highlighted row is
+    // always the setting Enter changes; notes sit between them unselectable
+    let mut rows: Vec<(Option<char>, Line)> = vec![
+        (None, Line::from(vec![Span::raw(" Settings ").bold()])),
+        (None, Line::from("")),
     ];
+    for &(key, field) in SETTING_KEYS.iter() {
+        let customized = app.customized_settings.contains(field);
+        let label = Span::raw(format!("{}[{}] {}: ", if customized { "•" } else { " " }, key, setting_label(key)));
+        let mut spans = vec![if customized { label.fg(Color::Magenta) } else { label }];
+        spans.extend(setting_value(app, key));
+        rows.push((Some(key), Line::from(spans)));
+        if let Some(note) = setting_note(key) {
+            rows.push((None, Line::from(note)));
+        }
+    }
+    let templates = [
+        ("Status Bar", if app.settings.status_template.is_empty() { "Default".to_string() } else { app.settings.status_template.clone() }),
+        ("New Draft Template", if app.settings.new_draft_template.is_empty() { "None".to_string() } else { app.settings.new_draft_template.lines().next().unwrap_or("").to_string() }),
+        ("Flow Draft Header", if app.settings.flow_entry_header.is_empty() { "None".to_string() } else { app.settings.flow_entry_header.clone() }),
+    ];
+    for (label, value) in templates {
+        rows.push((None, Line::from(vec![Span::raw(format!("  {}: ", label)), Span::raw(value).fg(Color::Cyan)])));
+    }
+    for line in [
+        "(Edit status_template, new_draft_template and flow_entry_header in settings.json)",
+        "(Template tokens: {words} {chars} {reading} {mode} {draft} {dirty} {misspelled} {wpm} {selection} {percent} {time} {session})",
+        "(• marks settings changed from the default; Backspace then a setting's key, or Enter, resets it)",
+        "",
+        " [↑/↓] Select | [Enter/Space] Change | [#] Type a number | [Esc] Back to Menu",
+    ] {
+        rows.push((None, Line::from(line)));
+    }
+
+    let selected_key = app.settings_state.selected().and_then(|i| SETTING_KEYS.get(i)).map(|(k, _)| *k);
+    let selected_row = rows.iter().position(|(key, _)| key.is_some() && *key == selected_key);
+    let items: Vec<ListItem> = rows.into_iter().map(|(_, line)| ListItem::new(line)).collect();
 
     let block = Block::default()
         .borders(Borders::ALL)
         .title(" Settings ");
-    let p = Paragraph::new(output).block(block);
-    f.render_widget(p, area);
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(theme(app.theme()).highlight);
+    let mut state = ListState::default().with_selected(selected_row);
+    f.render_stateful_widget(list, area, &mut state);
 }
 
-fn render_spellcheck(f: &mut Frame, app: &App, area: Rect) {
+/// Name the Settings screen shows for the setting with hotkey `key`
+fn setting_label(key: char) -> &'static str {
+    match key {
+        'e' => "Default Extension",
+        'v' => "Vim Mode",
+        's' => "Splash Screen",
+        'T' => "Dismiss Splash",
+        'i' => "Splash Content",
+        'C' => "Theme",
+        'c' => "Spell Check",
+        'a' => "Skip Code/Links in Markdown",
+        'f' => "Confirm Flow Exit",
+        'd' => "Save Flow as Draft",
+        'w' => "Flow Timer Warning",
+        'u' => "Flow Timer Caution",
+        'o' => "End Flow When Time Is Up",
+        'm' => "Flow Milestones",
+        'D' => "Dim When Idle in Flow",
+        'g' => "Streak Goal",
+        'h' => "Flow History Limit",
+        'p' => "Confirm Quit",
+        'O' => "Confirm Rename Overwrite",
+        'l' => "Tab Width",
+        'K' => "Tab Key Types",
+        'r' => "Expand Tabs on Save",
+        'E' => "Line Endings",
+        'z' => "Lock When Idle",
+        'y' => "Passphrase",
+        'A' => "Flow Crash Recovery",
+        'j' => "Word Log",
+        'L' => "Paragraph Spacing (display only)",
+        'G' => "Wrap Guide",
+        'B' => "Flow Timer",
+        'W' => "Words per Minute Meter",
+        'P' => "Print Session Summary on Quit",
+        'x' => "Save Work When Terminated",
+        't' => "Terminal Cursor (bar/block by mode)",
+        'b' => "Cursor Blink",
+        'n' => "Number Format",
+        'k' => "Keep Versions",
+        'M' => "Read-Only Above",
+        'S' => "Storage Path",
+        _ => "",
+    }
+}
+
+/// Explanation shown on its own line under a setting's row
+fn setting_note(key: char) -> Option<&'static str> {
+    match key {
+        'T' => Some("(Splash always shows on version upgrades)"),
+        'u' => Some("(Timer colors: flow_timer_color / flow_caution_color / flow_warn_color / flow_overtime_color in settings.json)"),
+        _ => None,
+    }
+}
+
+fn enabled_span(on: bool) -> Span<'static> {
+    Span::raw(if on { "Enabled" } else { "Disabled" }).bold().fg(if on { Color::Green } else { Color::Red })
+}
+
+/// The current value of the setting with hotkey `key`, as shown after its label
+fn setting_value(app: &App, key: char) -> Vec<Span<'static>> {
+    let s = &app.settings;
+    let value = match key {
+        'e' => Span::raw(s.default_extension.clone()).bold().fg(Color::Yellow),
+        'v' => {
+            return vec![
+                enabled_span(s.vim_mode),
+                Span::raw("  Leader: "),
+                Span::raw(if s.leader_key == ' ' { "Space".to_string() } else { s.leader_key.to_string() }).bold().fg(Color::Cyan),
+                Span::raw(format!(" ({} mappings, edit in settings.json)", s.leader_mappings.len())).fg(Color::DarkGray),
+            ];
+        }
+        's' => enabled_span(s.show_splash_screen),
+        'T' => Span::raw(if s.splash_auto_advance { "Any key or after 30s" } else { "Enter/Space only" }).bold().fg(Color::Yellow),
+        'i' => Span::raw(match s.splash_content {
+            SplashContent::Credits => "Credits",
+            SplashContent::Quote => "Writing quote",
+            SplashContent::Stats => "Last session stats",
+        }).bold().fg(Color::Yellow),
+        'C' => {
+            let name = Span::raw(match app.theme() {
+                ThemeName::Terminal => "Terminal colors",
+                ThemeName::Cream => "Cream",
+                ThemeName::Dusk => "Dusk",
+                ThemeName::Forest => "Forest",
+                ThemeName::Paper => "Paper (no colors)",
+            }).bold().fg(Color::Cyan);
+            if app.theme_preview.is_none() {
+                return vec![name];
+            }
+            return vec![name, Span::raw("  (previewing: leave Settings to keep it, Esc reverts)").fg(Color::Yellow)];
+        }
+        'c' => enabled_span(s.spellcheck_enabled),
+        'a' => enabled_span(s.spellcheck_markdown_aware),
+        'f' => enabled_span(s.confirm_flow_exit),
+        'd' => enabled_span(s.flow_save_as_draft),
+        'w' => Span::raw(format!("under {}s", s.flow_warn_secs)).bold().fg(parse_color(&s.flow_warn_color, Color::Red)),
+        'u' => if s.flow_caution_secs == 0 {
+            Span::raw("Disabled").bold().fg(Color::DarkGray)
+        } else {
+            Span::raw(format!("under {}s", s.flow_caution_secs)).bold().fg(parse_color(&s.flow_caution_color, Color::Yellow))
+        },
+        'o' => Span::raw(if s.flow_auto_end { "Enabled" } else { "Disabled (overtime)" }).bold().fg(if s.flow_auto_end { Color::Green } else { Color::Red }),
+        'm' => if s.flow_milestone_words == 0 {
+            Span::raw("Disabled").bold().fg(Color::Red)
+        } else {
+            Span::raw(format!("Every {} words", s.flow_milestone_words)).bold().fg(Color::Yellow)
+        },
+        'D' => if s.flow_idle_dim_secs == 0 {
+            Span::raw("Never").bold().fg(Color::DarkGray)
+        } else {
+            Span::raw(format!("After {}s", s.flow_idle_dim_secs)).bold().fg(Color::Yellow)
+        },
+        'g' => Span::raw(format!("{} words/day", s.streak_min_words)).bold().fg(Color::Yellow),
+        'h' => if s.max_history_entries == 0 {
+            Span::raw("Unlimited").bold().fg(Color::DarkGray)
+        } else {
+            Span::raw(format!("{} sessions, older ones archived by year", s.max_history_entries)).bold().fg(Color::Yellow)
+        },
+        'p' => Span::raw(if s.confirm_quit { "Enabled" } else { "Only with unsaved changes" }).bold().fg(if s.confirm_quit { Color::Green } else { Color::Yellow }),
+        'O' => Span::raw(if s.confirm_rename_overwrite { "Ask" } else { "Add a number" }).bold().fg(if s.confirm_rename_overwrite { Color::Green } else { Color::Yellow }),
+        'l' => Span::raw(format!("{} columns", s.tab_width)).bold().fg(Color::Cyan),
+        'K' => Span::raw(if s.soft_tabs { "Spaces" } else { "Tab character" }).bold().fg(Color::Cyan),
+        'r' => Span::raw(if s.expand_tabs_on_save { "On" } else { "Off" }).bold().fg(if s.expand_tabs_on_save { Color::Green } else { Color::DarkGray }),
+        'E' => Span::raw(match s.line_endings {
+            LineEndings::Auto => "Auto (as each file had them)",
+            LineEndings::Lf => "LF",
+            LineEndings::Crlf => "CRLF",
+        }).bold().fg(Color::Cyan),
+        'z' => if s.idle_lock_secs == 0 {
+            Span::raw("Never").bold().fg(Color::DarkGray)
+        } else if s.lock_passphrase.is_empty() {
+            Span::raw("Never (no passphrase)").bold().fg(Color::DarkGray)
+        } else {
+            Span::raw(format!("After {} min", s.idle_lock_secs / 60)).bold().fg(Color::Yellow)
+        },
+        'y' => Span::raw(if s.lock_passphrase.is_empty() { "Not set" } else { "Set" }).bold().fg(if s.lock_passphrase.is_empty() { Color::DarkGray } else { Color::Green }),
+        'A' => if s.flow_recovery_secs == 0 {
+            Span::raw("Off").bold().fg(Color::Red)
+        } else {
+            Span::raw(format!("Save every {}s", s.flow_recovery_secs)).bold().fg(Color::Green)
+        },
+        'j' => if s.word_log {
+            Span::raw(if s.word_log_path.is_empty() { "word_log.csv".to_string() } else { s.word_log_path.clone() }).bold().fg(Color::Green)
+        } else {
+            Span::raw("Disabled").bold().fg(Color::Red)
+        },
+        'L' => enabled_span(s.visual_paragraph_spacing),
+        'G' => enabled_span(s.wrap_guide),
+        'B' => Span::raw(match s.flow_timer_style {
+            FlowTimerStyle::Digits => "Digits",
+            FlowTimerStyle::Bar => "Progress bar",
+            FlowTimerStyle::Both => "Digits and progress bar",
+        }).bold().fg(Color::Cyan),
+        'W' => enabled_span(s.show_wpm),
+        'P' => enabled_span(s.print_summary),
+        'x' => enabled_span(s.save_on_signal),
+        't' => enabled_span(s.terminal_cursor),
+        'b' => Span::raw(if s.cursor_blink { "On" } else { "Off" }).bold().fg(if s.cursor_blink { Color::Green } else { Color::Red }),
+        'n' => Span::raw(match s.number_format {
+            NumberFormat::Plain => "Plain (1234)",
+            NumberFormat::Comma => "Comma (1,234)",
+            NumberFormat::Period => "Period (1.234)",
+            NumberFormat::Space => "Space (1 234)",
+        }).bold().fg(Color::Cyan),
+        'k' => if s.keep_versions == 0 {
+            Span::raw("Disabled").bold().fg(Color::Red)
+        } else {
+            Span::raw(format!("Last {}", s.keep_versions)).bold().fg(Color::Yellow)
+        },
+        'M' => if s.max_draft_kb == 0 {
+            Span::raw("No limit").bold().fg(Color::DarkGray)
+        } else {
+            Span::raw(format!("{} KB", s.max_draft_kb)).bold().fg(Color::Cyan)
+        },
+        'S' => Span::raw(s.storage_path.clone()).italic().fg(Color::Cyan),
+        _ => Span::raw(""),
+    };
+    vec![value]
+}
+
+fn render_spellcheck(f: &mut Frame, app: &mut App, area: Rect) {
     let mut output = vec![
         Line::from(vec![Span::raw(" Spell Check Results ").bold()]),
         Line::from(""),
@@ -310,38 +1546,79 @@ fn render_spellcheck(f: &mut Frame, app: &App, area: Rect) {
             Span::styled(format!("Found {} potentially misspelled word(s):", app.misspelled_words.len()), 
                 Style::default().fg(Color::Yellow))
         ]));
-        output.push(Line::from(""));
-        
-        for word in &app.misspelled_words {
-            output.push(Line::from(vec![
-                Span::raw("  • "),
-                Span::styled(word, Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-            ]));
-        }
     }
 
     output.push(Line::from(""));
-    output.push(Line::from(""));
-    output.push(Line::from(" [Esc] Back to Writing"));
+    output.push(Line::from(" [↑/↓] Select | [Enter/r] Replace all | [Esc] Back to Writing"));
 
     let block = Block::default()
         .borders(Borders::ALL)
         .title(" Spell Check ");
-    let p = Paragraph::new(output).block(block);
-    f.render_widget(p, area);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(output.len() as u16 + 1), Constraint::Min(0)])
+        .split(inner);
+    f.render_widget(Paragraph::new(output), chunks[0]);
+
+    let items: Vec<ListItem> = app.misspelled_words.iter().map(|word| {
+        ListItem::new(Line::from(vec![
+            Span::raw("  • "),
+            Span::styled(word.clone(), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        ]))
+    }).collect();
+    let list = List::new(items)
+        .highlight_style(theme(app.theme()).highlight);
+    f.render_stateful_widget(list, chunks[1], &mut app.spellcheck_state);
 }
 
-fn parse_markdown_to_lines(input: &str) -> Vec<Line<'static>> {
+// Bullet glyph per nesting level, repeating for deeper lists
+const BULLETS: [&str; 3] = ["•", "◦", "▪"];
+
+fn parse_markdown_to_lines(input: &str, width: u16) -> Vec<Line<'static>> {
     let mut lines = Vec::new();
     let mut current_spans = Vec::new();
     let mut style = Style::default();
+    // One entry per open list: the next number for ordered lists, None for bullets
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    // The current line holds only a list marker, waiting for the item's text
+    let mut marker_pending = false;
+    // Fence language and collected text of the code block being read
+    let mut code_block: Option<(String, String)> = None;
 
     // Enable basic features
     let parser = Parser::new(input);
     
     for event in parser {
         match event {
-            Event::Text(t) => current_spans.push(Span::styled(t.to_string(), style)),
+            Event::Text(t) if code_block.is_some() => {
+                if let Some((_, code)) = code_block.as_mut() {
+                    code.push_str(&t);
+                }
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                if !current_spans.is_empty() {
+                    lines.push(Line::from(current_spans.clone()));
+                    current_spans.clear();
+                }
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or("").to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code_block = Some((lang, String::new()));
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((lang, code)) = code_block.take() {
+                    lines.extend(highlight::code_block(&code, &lang));
+                    lines.push(Line::from(""));
+                }
+            }
+            Event::Text(t) => {
+                marker_pending = false;
+                current_spans.push(Span::styled(t.to_string(), style));
+            }
             Event::Code(c) => current_spans.push(Span::styled(c.to_string(), style.bg(Color::DarkGray).fg(Color::White))),
             Event::Start(Tag::Emphasis) => style = style.add_modifier(Modifier::ITALIC),
             Event::End(TagEnd::Emphasis) => style = style.remove_modifier(Modifier::ITALIC),
@@ -356,11 +1633,11 @@ fn parse_markdown_to_lines(input: &str) -> Vec<Line<'static>> {
                 current_spans.clear();
                 lines.push(Line::from("")); // Space after header
             }
-            Event::Start(Tag::Paragraph) => {
-                if !current_spans.is_empty() {
-                    lines.push(Line::from(current_spans.clone()));
-                    current_spans.clear();
-                }
+            // Items in loose lists wrap their text in a paragraph; keep it on the marker's line
+            Event::Start(Tag::Paragraph) if marker_pending => marker_pending = false,
+            Event::Start(Tag::Paragraph) if !current_spans.is_empty() => {
+                lines.push(Line::from(current_spans.clone()));
+                current_spans.clear();
             }
             Event::End(TagEnd::Paragraph) => {
                  lines.push(Line::from(current_spans.clone()));
@@ -374,11 +1651,40 @@ fn parse_markdown_to_lines(input: &str) -> Vec<Line<'static>> {
                 lines.push(Line::from(current_spans.clone()));
                 current_spans.clear();
             }
-            Event::Start(Tag::List(_)) | Event::End(TagEnd::List(_)) => {}
+            Event::Start(Tag::List(start)) => {
+                // A nested list starts inside its parent item, so finish that line first
+                if !current_spans.is_empty() {
+                    lines.push(Line::from(current_spans.clone()));
+                    current_spans.clear();
+                }
+                marker_pending = false;
+                lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+            }
             Event::Start(Tag::Item) => {
-                current_spans.push(Span::raw("• "));
+                let depth = lists.len().saturating_sub(1);
+                let marker = match lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => format!("{} ", BULLETS[depth % BULLETS.len()]),
+                };
+                current_spans.push(Span::raw(format!("{}{}", "  ".repeat(depth), marker)));
+                marker_pending = true;
             }
-            Event::End(TagEnd::Item) => {
+            Event::Rule => {
+                if !current_spans.is_empty() {
+                    lines.push(Line::from(current_spans.clone()));
+                    current_spans.clear();
+                }
+                lines.push(Line::styled("─".repeat(width as usize), Style::default().fg(Color::DarkGray)));
+                lines.push(Line::from(""));
+            }
+            // Items holding a nested list were already flushed when it started
+            Event::End(TagEnd::Item) if !current_spans.is_empty() => {
                  lines.push(Line::from(current_spans.clone()));
                  current_spans.clear();
             }
@@ -392,21 +1698,32 @@ fn parse_markdown_to_lines(input: &str) -> Vec<Line<'static>> {
 }
 
 fn render_popup(f: &mut Frame, app: &mut App, area: Rect) {
-    let block = Block::default().title("Popup").borders(Borders::ALL);
     let popup_area = centered_rect(60, 20, area);
     
     f.render_widget(Clear, popup_area);
     
+    let number_title;
     let title = match &app.popup_action {
         PopupAction::RenameDraft(_) => "Rename Draft (Enter new name)",
         PopupAction::NewDraftFromSelection(_) => "New Draft Name",
+        PopupAction::InsertFile => "Insert File (Enter path)",
+        PopupAction::SaveAs => "Save As (Enter new name)",
+        PopupAction::ReplaceWord(_) => "Replace All With",
+        PopupAction::JumpToPercent => "Jump To (% of draft)",
+        PopupAction::Find => "Find in Draft",
+        PopupAction::SetLockPassphrase => "Lock Passphrase (empty clears it and turns the lock off)",
+        PopupAction::SetStoragePath => "Storage Path",
+        PopupAction::SetNumber(field) => {
+            number_title = format!("{} (whole number)", field);
+            &number_title
+        }
         _ => "Input",
     };
 
     app.popup_textarea.set_block(
         Block::default()
             .borders(Borders::ALL)
-            .title(title),
+            .title(title.to_string()),
     );
     f.render_widget(&app.popup_textarea, popup_area);
 }
@@ -430,3 +1747,34 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         ])
         .split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scroll_moves_only_to_keep_the_cursor_visible() {
+        assert_eq!(next_scroll_start(0, 5, 10), 0);
+        assert_eq!(next_scroll_start(0, 10, 10), 1);
+        assert_eq!(next_scroll_start(4, 12, 10), 4);
+        assert_eq!(next_scroll_start(4, 2, 10), 2);
+    }
+
+    #[test]
+    fn long_messages_fit_narrow_terminals() {
+        let msg = "Press a setting's key, or Enter for the highlighted one, to reset it";
+        assert_eq!(message_rect(msg, Rect::new(0, 0, 40, 10)), Rect::new(0, 9, 40, 1));
+        assert_eq!(message_rect("Saved ✓", Rect::new(0, 0, 80, 24)), Rect::new(69, 23, 11, 1));
+        assert_eq!(message_rect(msg, Rect::new(0, 0, 0, 0)), Rect::new(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn selection_columns_cover_line_breaks_between_rows() {
+        let range = ((1, 2), (3, 4));
+        assert_eq!(selected_columns(range, 0, 8), None);
+        assert_eq!(selected_columns(range, 1, 8), Some((2, 9)));
+        assert_eq!(selected_columns(range, 2, 0), Some((0, 1)));
+        assert_eq!(selected_columns(range, 3, 8), Some((0, 4)));
+        assert_eq!(selected_columns(((1, 2), (3, 0)), 3, 8), None);
+    }
+}